@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use synthia::song::load_from_str;
+
+// Malformed song files must be rejected with an error, never a panic
+fuzz_target!(|data: &[u8]| {
+    if let Ok(json) = std::str::from_utf8(data) {
+        let _ = load_from_str(json);
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use synthia::song::parse_midi;
+
+// Truncated or corrupt MIDI files must be rejected with an error, never a panic
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_midi(data);
+});
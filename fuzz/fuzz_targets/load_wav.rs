@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+use synthia::utils::{read_wav, read_wav_tags_from};
+
+// Corrupt WAV files, and their INFO and ID3 tag chunks, must be rejected with an
+// error, never a panic or an allocation the file doesn't back
+fuzz_target!(|data: &[u8]| {
+    let _ = read_wav(Cursor::new(data));
+    let _ = read_wav_tags_from(Cursor::new(data));
+});
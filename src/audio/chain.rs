@@ -0,0 +1,30 @@
+use std::f32::consts::FRAC_PI_2;
+
+// Join rendered songs back-to-back without gaps, optionally overlapping
+// consecutive songs with an equal-power crossfade of `crossfade_frames`.
+// The waveforms hold interleaved frames of `channels` samples.
+pub fn chain_waveforms(waveforms: &[Vec<f32>], channels: u16, crossfade_frames: usize) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let total: usize = waveforms.iter().map(Vec::len).sum();
+    let mut chained: Vec<f32> = Vec::with_capacity(total);
+
+    for waveform in waveforms {
+        // The fade can't be longer than either side of the join
+        let fade = crossfade_frames.min(chained.len() / channels).min(waveform.len() / channels);
+        let overlap_start = chained.len() - fade * channels;
+
+        for i in 0..fade {
+            let position = (i as f32 + 0.5) / fade as f32;
+            let fade_out = (position * FRAC_PI_2).cos();
+            let fade_in = (position * FRAC_PI_2).sin();
+            for channel in 0..channels {
+                let index = i * channels + channel;
+                chained[overlap_start + index] = chained[overlap_start + index] * fade_out + waveform[index] * fade_in;
+            }
+        }
+
+        chained.extend_from_slice(&waveform[fade * channels..]);
+    }
+
+    chained
+}
@@ -0,0 +1,81 @@
+use crate::units::linear_to_db;
+
+// Result of null-testing one render against another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub offset: isize,          // samples `compared` is shifted by to line up with `reference`
+    pub compared_samples: usize,
+    pub reference_rms_db: f32,
+    pub residual_rms_db: f32,
+    pub residual_peak_db: f32,
+}
+
+impl Comparison {
+    // Residual level relative to the reference; very negative means the renders null
+    pub fn relative_residual_db(&self) -> f32 {
+        self.residual_rms_db - self.reference_rms_db
+    }
+}
+
+// Samples from the start of each file used to find the alignment
+const ALIGNMENT_WINDOW: usize = 65536;
+
+// Align `compared` to `reference` within +-max_offset samples, subtract and measure the difference
+pub fn compare_waveforms(reference: &[f32], compared: &[f32], max_offset: usize) -> Comparison {
+    let offset = best_offset(reference, compared, max_offset);
+
+    let mut residual_energy = 0.0f64;
+    let mut reference_energy = 0.0f64;
+    let mut residual_peak = 0.0f32;
+    let mut compared_samples = 0;
+
+    for (i, &sample) in reference.iter().enumerate() {
+        let j = i as isize + offset;
+        if j < 0 || j as usize >= compared.len() {
+            continue;
+        }
+        let residual = sample - compared[j as usize];
+        residual_energy += (residual * residual) as f64;
+        reference_energy += (sample * sample) as f64;
+        residual_peak = residual_peak.max(residual.abs());
+        compared_samples += 1;
+    }
+
+    let count = compared_samples.max(1) as f64;
+    Comparison {
+        offset,
+        compared_samples,
+        reference_rms_db: linear_to_db((reference_energy / count).sqrt() as f32),
+        residual_rms_db: linear_to_db((residual_energy / count).sqrt() as f32),
+        residual_peak_db: linear_to_db(residual_peak),
+    }
+}
+
+// Lag with the highest cross-correlation over the start of both signals
+fn best_offset(reference: &[f32], compared: &[f32], max_offset: usize) -> isize {
+    let window = reference.len().min(ALIGNMENT_WINDOW);
+    let max_offset = max_offset as isize;
+
+    let mut best = (0isize, f32::MIN);
+    for offset in -max_offset..=max_offset {
+        let correlation: f32 = (0..window)
+            .filter_map(|i| {
+                let j = i as isize + offset;
+                compared.get(usize::try_from(j).ok()?).map(|&other| reference[i] * other)
+            })
+            .sum();
+        // Prefer the smallest shift on ties, e.g. for silent files
+        if correlation > best.1 || (correlation == best.1 && offset.abs() < best.0.abs()) {
+            best = (offset, correlation);
+        }
+    }
+    best.0
+}
+
+// FNV-1a hash of the exact sample values, for checking that two renders are bit-identical
+pub fn waveform_hash(waveform: &[f32]) -> u64 {
+    waveform
+        .iter()
+        .flat_map(|sample| sample.to_bits().to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,8 @@
+// Filter and feedback state below this is flushed to zero. A decaying tail would
+// otherwise reach denormal numbers, which are many times slower to compute with on most CPUs.
+const DENORMAL_THRESHOLD: f64 = 1e-20;
+
+// For f32 and f64 state alike
+pub(super) fn flush_denormal<T: Into<f64> + Copy + Default>(value: T) -> T {
+    if value.into().abs() < DENORMAL_THRESHOLD { T::default() } else { value }
+}
@@ -0,0 +1,44 @@
+use crate::song::EffectSettings;
+use super::effect::{Effect, build_effect};
+
+// Effects run one after the other on the same buffer
+#[derive(Default)]
+pub struct EffectChain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl EffectChain {
+    pub fn new() -> Self {
+        EffectChain::default()
+    }
+
+    // The effects of a song, in order. Custom effects that aren't registered are left
+    // out; their names are returned with the chain.
+    pub fn from_settings(settings: &[EffectSettings]) -> (Self, Vec<String>) {
+        let mut chain = EffectChain::new();
+        let mut missing = Vec::new();
+        for settings in settings {
+            match build_effect(settings) {
+                Some(effect) => chain.push(effect),
+                None => missing.push(settings.name().to_string()),
+            }
+        }
+        (chain, missing)
+    }
+
+    pub fn push(&mut self, effect: Box<dyn Effect>) {
+        self.effects.push(effect);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+}
+
+impl Effect for EffectChain {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32) {
+        for effect in self.effects.iter_mut() {
+            effect.process(buffer, sample_rate);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::audio::CHANNELS;
+use crate::audio::denormal::flush_denormal;
+use crate::song::MAX_DELAY_TIME;
+use crate::units::seconds_to_samples;
+use super::effect::Effect;
+
+// Feedback is kept below this so the echoes always die away
+const MAX_FEEDBACK: f32 = 0.95;
+
+// Echoes of the mix, every channel echoing itself
+pub struct Delay {
+    time: f32,
+    feedback: f32,
+    mix: f32,
+    line: Vec<f32>,  // interleaved like the mix, sized for the sample rate it was last used at
+    position: usize,
+    sample_rate: u32,
+}
+
+impl Delay {
+    pub fn new(time: f32, feedback: f32, mix: f32) -> Self {
+        let time = if time.is_nan() { 0.0 } else { time.clamp(0.0, MAX_DELAY_TIME) };
+        let feedback = if feedback.is_nan() { 0.0 } else { feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK) };
+        // A mix that isn't a number would turn every sample into one
+        let mix = if mix.is_finite() { mix } else { 0.0 };
+        Delay { time, feedback, mix, line: Vec::new(), position: 0, sample_rate: 0 }
+    }
+}
+
+impl Effect for Delay {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.line = vec![0.0; seconds_to_samples(self.time, sample_rate) * CHANNELS as usize];
+            self.position = 0;
+        }
+        // Too short to echo
+        if self.line.is_empty() {
+            return;
+        }
+
+        for sample in buffer {
+            let delayed = self.line[self.position];
+            self.line[self.position] = flush_denormal(*sample + self.feedback * delayed);
+            *sample += self.mix * delayed;
+            self.position = (self.position + 1) % self.line.len();
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::song::EffectSettings;
+use super::registry::registered_effect;
+use super::gain::Gain;
+use super::filter::Filter;
+use super::delay::Delay;
+
+// Processes a rendered mix in place. The buffer holds interleaved frames of
+// `audio::CHANNELS` samples. Effects keep their state between calls, so a mix can be
+// processed in one go or a piece at a time.
+pub trait Effect: Send {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32);
+}
+
+// The effect a song describes, or None for a custom effect that isn't registered
+pub fn build_effect(settings: &EffectSettings) -> Option<Box<dyn Effect>> {
+    let effect: Box<dyn Effect> = match settings {
+        EffectSettings::Gain { db } => Box::new(Gain::new(*db)),
+        EffectSettings::LowPass { cutoff } => Box::new(Filter::low_pass(*cutoff)),
+        EffectSettings::HighPass { cutoff } => Box::new(Filter::high_pass(*cutoff)),
+        EffectSettings::Delay { time, feedback, mix } => Box::new(Delay::new(*time, *feedback, *mix)),
+        EffectSettings::Custom { name, params } => registered_effect(name)?(params),
+    };
+    Some(effect)
+}
@@ -0,0 +1,61 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use crate::audio::CHANNELS;
+use crate::audio::denormal::flush_denormal;
+use super::effect::Effect;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Response {
+    LowPass,
+    HighPass,
+}
+
+// A 12 dB per octave Butterworth filter (a biquad in direct form I), every channel with
+// its own state
+pub struct Filter {
+    response: Response,
+    cutoff: f32,
+    state: [[f32; 4]; CHANNELS as usize],  // x1, x2, y1, y2
+}
+
+impl Filter {
+    pub fn low_pass(cutoff: f32) -> Self {
+        Filter { response: Response::LowPass, cutoff, state: Default::default() }
+    }
+
+    pub fn high_pass(cutoff: f32) -> Self {
+        Filter { response: Response::HighPass, cutoff, state: Default::default() }
+    }
+
+    // Normalized b0, b1, b2, a1, a2. The cutoff is kept between 1 Hz and just below
+    // the Nyquist frequency.
+    fn coefficients(&self, sample_rate: u32) -> [f32; 5] {
+        let cutoff = self.cutoff.max(1.0).min(sample_rate as f32 * 0.49);
+        let omega = 2.0 * PI * cutoff / sample_rate as f32;
+        let alpha = omega.sin() / (2.0 * FRAC_1_SQRT_2);
+        let cos = omega.cos();
+
+        let (b0, b1) = match self.response {
+            Response::LowPass => ((1.0 - cos) / 2.0, 1.0 - cos),
+            Response::HighPass => ((1.0 + cos) / 2.0, -(1.0 + cos)),
+        };
+        let a0 = 1.0 + alpha;
+        [b0 / a0, b1 / a0, b0 / a0, -2.0 * cos / a0, (1.0 - alpha) / a0]
+    }
+}
+
+impl Effect for Filter {
+    fn process(&mut self, buffer: &mut [f32], sample_rate: u32) {
+        if sample_rate == 0 {
+            return;
+        }
+        let [b0, b1, b2, a1, a2] = self.coefficients(sample_rate);
+        for frame in buffer.chunks_exact_mut(CHANNELS as usize) {
+            for (sample, [x1, x2, y1, y2]) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let x = *sample;
+                let y = flush_denormal(b0 * x + b1 * *x1 + b2 * *x2 - a1 * *y1 - a2 * *y2);
+                (*x2, *x1, *y2, *y1) = (*x1, x, *y1, y);
+                *sample = y;
+            }
+        }
+    }
+}
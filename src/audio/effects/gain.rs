@@ -0,0 +1,20 @@
+use crate::units::db_to_linear;
+use super::effect::Effect;
+
+pub struct Gain {
+    gain: f32,
+}
+
+impl Gain {
+    pub fn new(db: f32) -> Self {
+        Gain { gain: db_to_linear(db) }
+    }
+}
+
+impl Effect for Gain {
+    fn process(&mut self, buffer: &mut [f32], _sample_rate: u32) {
+        for sample in buffer {
+            *sample *= self.gain;
+        }
+    }
+}
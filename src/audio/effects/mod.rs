@@ -0,0 +1,13 @@
+mod effect;
+mod chain;
+mod registry;
+mod gain;
+mod filter;
+mod delay;
+
+pub use effect::{Effect, build_effect};
+pub use chain::EffectChain;
+pub use registry::{EffectFactory, register_effect, unregister_effect, registered_effect};
+pub use gain::Gain;
+pub use filter::Filter;
+pub use delay::Delay;
@@ -0,0 +1,26 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use super::effect::Effect;
+
+// Builds a custom effect from the parameters a song gives it
+pub type EffectFactory = Arc<dyn Fn(&BTreeMap<String, f32>) -> Box<dyn Effect> + Send + Sync>;
+
+static REGISTRY: OnceLock<RwLock<BTreeMap<String, EffectFactory>>> = OnceLock::new();
+
+fn registry() -> &'static RwLock<BTreeMap<String, EffectFactory>> {
+    REGISTRY.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+// Make an effect available to songs as `{"Custom": {"name": name, "params": {...}}}`
+// Registering an existing name replaces it
+pub fn register_effect(name: &str, factory: EffectFactory) {
+    registry().write().unwrap().insert(name.to_string(), factory);
+}
+
+pub fn unregister_effect(name: &str) {
+    registry().write().unwrap().remove(name);
+}
+
+pub fn registered_effect(name: &str) -> Option<EffectFactory> {
+    registry().read().unwrap().get(name).cloned()
+}
@@ -0,0 +1,33 @@
+use crate::song::{Envelope, Instrument};
+
+// Envelopes for instruments without one in the song. Just enough attack and release
+// that notes don't click; the piano shapes its own decay.
+pub fn default_envelope(instrument: &Instrument) -> Envelope {
+    match instrument {
+        Instrument::Piano => Envelope { attack: 0.002, decay: 0.0, sustain: 1.0, release: 0.05 },
+        _ => Envelope { attack: 0.005, decay: 0.0, sustain: 1.0, release: 0.01 },
+    }
+}
+
+// Level of the envelope `time` seconds into a note that is let go after `held` seconds.
+// A note released during its attack or decay fades out from where it got to.
+pub fn envelope_level(envelope: &Envelope, time: f32, held: f32) -> f32 {
+    if time < held {
+        return held_level(envelope, time);
+    }
+    if envelope.release <= 0.0 {
+        return 0.0;
+    }
+    held_level(envelope, held) * (1.0 - (time - held) / envelope.release).max(0.0)
+}
+
+fn held_level(envelope: &Envelope, time: f32) -> f32 {
+    let sustain = envelope.sustain.clamp(0.0, 1.0);
+    if time < envelope.attack {
+        time / envelope.attack
+    } else if time < envelope.attack + envelope.decay {
+        1.0 - (1.0 - sustain) * (time - envelope.attack) / envelope.decay
+    } else {
+        sustain
+    }
+}
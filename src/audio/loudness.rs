@@ -0,0 +1,109 @@
+use std::f64::consts::PI;
+use super::denormal::flush_denormal;
+
+// ReplayGain 2.0 plays everything back at this loudness
+const REPLAY_GAIN_REFERENCE_LUFS: f32 = -18.0;
+
+// Gating blocks of 400 ms, overlapping by 75%
+const BLOCK_SECONDS: f64 = 0.4;
+const BLOCK_STEP_SECONDS: f64 = 0.1;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+// A biquad filter section in direct form I
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+}
+
+impl Biquad {
+    fn process(&self, input: &[f64]) -> Vec<f64> {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        input
+            .iter()
+            .map(|&x| {
+                let y = self.b[0] * x + self.b[1] * x1 + self.b[2] * x2 - self.a[1] * y1 - self.a[2] * y2;
+                let y = flush_denormal(y);
+                (x2, x1, y2, y1) = (x1, x, y1, y);
+                y
+            })
+            .collect()
+    }
+}
+
+// The K-weighting of ITU-R BS.1770 (a high shelf, then a high pass), designed for any
+// sample rate from the analog prototype rather than using the 48 kHz coefficients
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let sample_rate = sample_rate as f64;
+
+    let k = (PI * 1681.974450955533 / sample_rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    let k = (PI * 38.13547087602444 / sample_rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    [shelf, high_pass]
+}
+
+// Gated integrated loudness in LUFS (ITU-R BS.1770 / EBU R128) of interleaved frames of
+// `channels` samples, every channel weighted the same as for left and right.
+// Silence is negative infinity.
+pub fn integrated_loudness(waveform: &[f32], channels: u16, sample_rate: u32) -> f32 {
+    let channels = channels.max(1) as usize;
+    let frames = waveform.len() / channels;
+    if frames == 0 || sample_rate == 0 {
+        return f32::NEG_INFINITY;
+    }
+
+    // Squared K-weighted level of every frame, summed over the channels
+    let [shelf, high_pass] = k_weighting(sample_rate);
+    let mut energy = vec![0.0f64; frames];
+    for channel in 0..channels {
+        let input: Vec<f64> = waveform.iter().skip(channel).step_by(channels).take(frames).map(|&sample| sample as f64).collect();
+        for (energy, x) in energy.iter_mut().zip(high_pass.process(&shelf.process(&input))) {
+            *energy += x * x;
+        }
+    }
+
+    // Mean square of every block; a signal shorter than one block is a single block
+    let block = ((BLOCK_SECONDS * sample_rate as f64) as usize).min(frames);
+    let step = ((BLOCK_STEP_SECONDS * sample_rate as f64) as usize).max(1);
+    let powers: Vec<f64> = (0..=frames - block)
+        .step_by(step)
+        .map(|start| energy[start..start + block].iter().sum::<f64>() / block as f64)
+        .collect();
+
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let gated_mean = |threshold: f64| {
+        let gated: Vec<f64> = powers.iter().copied().filter(|&power| loudness(power) > threshold).collect();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+
+    let Some(absolute) = gated_mean(ABSOLUTE_GATE_LUFS) else {
+        return f32::NEG_INFINITY;
+    };
+    let relative = gated_mean(loudness(absolute) + RELATIVE_GATE_LU).unwrap_or(absolute);
+    loudness(relative) as f32
+}
+
+// ReplayGain 2.0 track gain in dB and track peak (linear sample peak), or None for silence
+pub fn replay_gain(waveform: &[f32], channels: u16, sample_rate: u32) -> Option<(f32, f32)> {
+    let loudness = integrated_loudness(waveform, channels, sample_rate);
+    if !loudness.is_finite() {
+        return None;
+    }
+    let peak = waveform.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    Some((REPLAY_GAIN_REFERENCE_LUFS - loudness, peak))
+}
@@ -1,5 +1,36 @@
 mod waveform;
 mod player;
+mod chain;
+mod modulation;
+mod test_signal;
+mod compare;
+mod one_shot;
+mod transport;
+mod true_peak;
+mod loudness;
+mod profile;
+mod report;
+mod overtones;
+mod stream;
+mod quality;
+mod envelope;
+mod voice;
+mod voice_pool;
+mod denormal;
+pub mod effects;
 
-pub use waveform::generate_wave_from_packets;
-pub use player::play_waveform;
+pub use waveform::{CHANNELS, generate_wave_from_packets, generate_stereo_wave_from_packets, generate_wave_from_song, render_song, render_song_profiled, render_song_with_report};
+pub use player::{play_waveform, play_interleaved, play_file, PlayFileError, Player, PlayerEvent};
+pub use chain::chain_waveforms;
+pub use test_signal::{TestSignal, generate_test_signal};
+pub use compare::{Comparison, compare_waveforms, waveform_hash};
+pub use one_shot::{used_notes, render_one_shot};
+pub use transport::{Transport, PlayState};
+pub use true_peak::true_peak;
+pub use loudness::{integrated_loudness, replay_gain};
+pub use profile::{RenderProfile, InstrumentCost};
+pub use report::{RenderReport, ReportedNote, TruncatedTail, ClippedRegion};
+pub use overtones::{Partial, OvertoneTable, overtone_table, set_overtones, add_partial, remove_partial, load_overtones, reload_changed_overtones, overtone_errors};
+pub use stream::serve_stream;
+pub use quality::RenderQuality;
+pub use envelope::{default_envelope, envelope_level};
@@ -0,0 +1,186 @@
+use crate::song::{Lfo, LfoShape, LfoRate, ModulationRoute, ModulationTarget, Instrument, Morph, TempoMap};
+use crate::utils::random_bipolar;
+
+use std::f32::consts::PI;
+
+// A route with its LFO resolved and its rate converted to Hz
+#[derive(Clone)]
+struct ResolvedRoute {
+    shape: LfoShape,
+    rate_hz: f32,
+    target: ModulationTarget,
+    depth: f32,
+    seed: u64,  // for random LFOs
+}
+
+// A morph with its region converted to seconds
+#[derive(Clone)]
+struct ResolvedMorph {
+    instrument: Instrument,
+    to: Instrument,
+    start: f32,
+    end: f32,
+}
+
+// Slow random pitch wander of a single voice
+#[derive(Clone)]
+struct VoiceDrift {
+    cents: f32,
+    rate_hz: f32,
+    seed: u64,
+}
+
+// Evaluates the song's modulation matrix and instrument morphs at any point in song time
+#[derive(Clone)]
+pub struct Modulation {
+    routes: Vec<ResolvedRoute>,
+    morphs: Vec<ResolvedMorph>,
+    legato: Vec<(f32, f32)>,  // (song time, semitones) pitch steps of a legato voice
+    glide: f32,
+    drift: Option<VoiceDrift>,
+}
+
+impl Modulation {
+    pub fn none() -> Self {
+        Modulation { routes: Vec::new(), morphs: Vec::new(), legato: Vec::new(), glide: 0.0, drift: None }
+    }
+
+    // Routes referring to an LFO that isn't defined, or whose rate isn't a number of Hz
+    // (like a cycle of 0 beats), are ignored. LFO rates in beats follow the tempo the song
+    // starts at. Random LFOs are seeded from the song's seed and their name, so each one
+    // wanders differently.
+    pub fn new(lfos: &[Lfo], routes: &[ModulationRoute], morphs: &[Morph], tempo: &TempoMap, seed: u64) -> Self {
+        let bpm = tempo.bpm();
+        let routes = routes
+            .iter()
+            .filter_map(|route| {
+                let lfo = lfos.iter().find(|lfo| lfo.name == route.lfo)?;
+                let rate_hz = match lfo.rate {
+                    LfoRate::Hz(hz) => hz,
+                    LfoRate::Beats(beats) => bpm / 60.0 / beats,
+                };
+                if !rate_hz.is_finite() {
+                    return None;
+                }
+                Some(ResolvedRoute {
+                    shape: lfo.shape.clone(),
+                    rate_hz,
+                    target: route.target.clone(),
+                    depth: route.depth,
+                    seed: lfo_seed(seed, &lfo.name),
+                })
+            })
+            .collect();
+
+        let morphs = morphs
+            .iter()
+            .map(|morph| ResolvedMorph {
+                instrument: morph.instrument.clone(),
+                to: morph.to.clone(),
+                start: tempo.seconds_at(morph.start) as f32,
+                end: tempo.seconds_at(morph.end) as f32,
+            })
+            .collect();
+
+        Modulation { routes, morphs, legato: Vec::new(), glide: 0.0, drift: None }
+    }
+
+    // A copy that also moves the pitch by the given semitone steps, each reached
+    // over `glide` seconds from the previous one
+    pub fn with_legato(&self, legato: Vec<(f32, f32)>, glide: f32) -> Self {
+        Modulation { legato, glide, ..self.clone() }
+    }
+
+    // A copy that also lets the pitch wander by up to `cents`, smoothly changing
+    // direction about `rate_hz` times a second. Each seed wanders differently.
+    pub fn with_drift(&self, cents: f32, rate_hz: f32, seed: u64) -> Self {
+        Modulation { drift: Some(VoiceDrift { cents, rate_hz, seed }), ..self.clone() }
+    }
+
+    // Frequency multiplier from all pitch routes at the given song time
+    pub fn pitch_ratio(&self, song_time: f32) -> f32 {
+        let semitones: f32 = self
+            .routes
+            .iter()
+            .filter(|route| route.target == ModulationTarget::Pitch)
+            .map(|route| route.depth * lfo_value(route, song_time))
+            .sum();
+        2.0f32.powf((semitones + self.legato_semitones(song_time) + self.drift_semitones(song_time)) / 12.0)
+    }
+
+    // Gain multiplier from all amplitude routes, between 1 - depth and 1
+    pub fn amplitude_gain(&self, song_time: f32) -> f32 {
+        self.routes
+            .iter()
+            .filter(|route| route.target == ModulationTarget::Amplitude)
+            .map(|route| 1.0 + route.depth * (lfo_value(route, song_time) - 1.0) / 2.0)
+            .product()
+    }
+
+    // The instrument this one morphs into, if any
+    pub fn morph_target(&self, instrument: &Instrument) -> Option<&Instrument> {
+        self.find_morph(instrument).map(|morph| &morph.to)
+    }
+
+    // How far an instrument has morphed into its target, from 0 to 1
+    pub fn morph_amount(&self, instrument: &Instrument, song_time: f32) -> f32 {
+        match self.find_morph(instrument) {
+            Some(morph) if song_time >= morph.end => 1.0,
+            Some(morph) if song_time > morph.start => (song_time - morph.start) / (morph.end - morph.start),
+            _ => 0.0,
+        }
+    }
+
+    fn find_morph(&self, instrument: &Instrument) -> Option<&ResolvedMorph> {
+        self.morphs.iter().find(|morph| morph.instrument == *instrument)
+    }
+
+    fn drift_semitones(&self, song_time: f32) -> f32 {
+        match &self.drift {
+            Some(drift) => drift.cents / 100.0 * smooth_noise(drift.seed, drift.rate_hz * song_time),
+            None => 0.0,
+        }
+    }
+
+    // Legato pitch offset, gliding linearly between steps. A step that comes before
+    // the previous glide has finished starts from wherever that glide got to.
+    fn legato_semitones(&self, song_time: f32) -> f32 {
+        let mut semitones = 0.0;
+        for (index, &(time, target)) in self.legato.iter().enumerate() {
+            if song_time < time {
+                break;
+            }
+            let until = self.legato.get(index + 1).map_or(song_time, |&(next, _)| next.min(song_time));
+            let progress = if self.glide > 0.0 { ((until - time) / self.glide).min(1.0) } else { 1.0 };
+            semitones += (target - semitones) * progress;
+        }
+        semitones
+    }
+}
+
+// Random values in [-1, 1] at whole positions, eased into each other in between
+fn smooth_noise(seed: u64, position: f32) -> f32 {
+    let index = position.floor();
+    let from = random_bipolar(seed, index as i64 as u64);
+    let to = random_bipolar(seed, (index as i64 + 1) as u64);
+    let progress = (1.0 - (PI * (position - index)).cos()) / 2.0;
+    from + (to - from) * progress
+}
+
+// LFO output in [-1, 1]
+fn lfo_value(route: &ResolvedRoute, time: f32) -> f32 {
+    let cycles = route.rate_hz * time;
+    let phase = cycles.fract();
+
+    match route.shape {
+        LfoShape::Sine => (2.0 * PI * phase).sin(),
+        LfoShape::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        // Sample & hold: a new random value every cycle, reproducible between renders
+        LfoShape::Random => random_bipolar(route.seed, cycles.floor() as i64 as u64),
+    }
+}
+
+// The song's seed mixed with an FNV-1a hash of the LFO's name
+fn lfo_seed(seed: u64, name: &str) -> u64 {
+    seed ^ name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
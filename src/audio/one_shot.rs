@@ -0,0 +1,51 @@
+use std::borrow::Cow;
+use crate::song::{Beats, Instrument, MidiPacket, NoteStatus, Song};
+use crate::units::{db_to_linear, seconds_to_samples};
+use super::modulation::Modulation;
+use super::voice::{generate_waveform, NoteSettings};
+use super::quality::RenderQuality;
+
+const ONE_SHOT_ATTACK_SECS: f32 = 0.005;
+const ONE_SHOT_RELEASE_SECS: f32 = 0.05;
+const ONE_SHOT_PEAK_DB: f32 = -1.0;
+
+// Every (instrument, pitch) pair a song plays, in order of first use
+pub fn used_notes(song: &Song) -> Vec<(Instrument, u8)> {
+    let mut notes: Vec<(Instrument, u8)> = Vec::new();
+    for packet in song.mixed_packets().iter().filter(|packet| packet.note_status == NoteStatus::On) {
+        if !notes.iter().any(|(instrument, pitch)| *instrument == packet.instrument && *pitch == packet.pitch) {
+            notes.push((packet.instrument.clone(), packet.pitch));
+        }
+    }
+    notes
+}
+
+// Render a single note as a standalone sample: held for `length_secs` with a short
+// fade in and out so it doesn't click, and normalized to a -1 dBFS peak
+pub fn render_one_shot(instrument: &Instrument, pitch: u8, length_secs: f32, sample_rate: u32) -> Vec<f32> {
+    let sample_amount = seconds_to_samples(length_secs, sample_rate);
+    let packet = MidiPacket::new(pitch, instrument.clone(), NoteStatus::On, Beats::ZERO, 1.0);
+
+    let note = NoteSettings { start_time: 0.0, detune_cents: 0.0, phase: 0.0, modulation: Cow::Owned(Modulation::none()), quality: RenderQuality::Final, envelope: None };
+    let mut samples = generate_waveform(&packet, sample_amount, sample_rate, &note);
+    samples.resize(sample_amount, 0.0);
+
+    let attack = seconds_to_samples(ONE_SHOT_ATTACK_SECS, sample_rate).min(sample_amount);
+    let release = seconds_to_samples(ONE_SHOT_RELEASE_SECS, sample_rate).min(sample_amount - attack);
+    for (i, sample) in samples.iter_mut().take(attack).enumerate() {
+        *sample *= i as f32 / attack as f32;
+    }
+    for (i, sample) in samples.iter_mut().rev().take(release).enumerate() {
+        *sample *= i as f32 / release as f32;
+    }
+
+    let peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    if peak > 0.0 {
+        let gain = db_to_linear(ONE_SHOT_PEAK_DB) / peak;
+        for sample in samples.iter_mut() {
+            *sample *= gain;
+        }
+    }
+
+    samples
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
+
+// One sine component of an additive instrument
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Partial {
+    pub ratio: f32,      // frequency as a multiple of the note's frequency
+    pub amplitude: f32,
+}
+
+pub type OvertoneTable = Arc<Vec<Partial>>;
+
+// The CSV a table was loaded from, to notice when it has been edited
+struct Source {
+    path: String,
+    modified: Option<SystemTime>,
+}
+
+struct Entry {
+    table: OvertoneTable,
+    source: Option<Source>,
+    error: Option<String>,  // why the CSV couldn't be loaded, if the built-in table plays instead
+}
+
+static OVERTONES: OnceLock<RwLock<BTreeMap<String, Entry>>> = OnceLock::new();
+
+fn overtones() -> &'static RwLock<BTreeMap<String, Entry>> {
+    OVERTONES.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+// The default tables: a CSV in the working directory overrides the copy built into
+// the binary, so Synthia runs from anywhere
+fn default_source(instrument: &str) -> Option<(&'static str, &'static str)> {
+    match instrument {
+        "Piano" => Some(("piano_overtones.csv", include_str!("../../piano_overtones.csv"))),
+        _ => None,
+    }
+}
+
+// The overtone table of an instrument, empty if it has none. Renders take a table
+// once per note, so edits made while a note renders apply from the next note on.
+pub fn overtone_table(instrument: &str) -> OvertoneTable {
+    if let Some(entry) = overtones().read().unwrap().get(instrument) {
+        return entry.table.clone();
+    }
+
+    let Some((path, built_in)) = default_source(instrument) else {
+        return Arc::new(Vec::new());
+    };
+    let mut error = None;
+    if std::path::Path::new(path).exists() {
+        match load_overtones(instrument, path) {
+            Ok(()) => return overtones().read().unwrap()[instrument].table.clone(),
+            Err(load_error) => error = Some(format!("{}: {}", path, load_error)),
+        }
+    }
+
+    // Keep the path, so creating or fixing the file later is picked up as an edit
+    let table = Arc::new(parse_overtones(built_in).expect("Built-in overtone table is invalid."));
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let source = Some(Source { path: path.to_string(), modified });
+    overtones().write().unwrap().insert(instrument.to_string(), Entry { table: table.clone(), source, error });
+    table
+}
+
+// Replace an instrument's partials
+pub fn set_overtones(instrument: &str, partials: Vec<Partial>) {
+    overtones().write().unwrap().insert(instrument.to_string(), Entry { table: Arc::new(partials), source: None, error: None });
+}
+
+pub fn add_partial(instrument: &str, partial: Partial) {
+    let mut partials = overtone_table(instrument).to_vec();
+    partials.push(partial);
+    update(instrument, partials);
+}
+
+pub fn remove_partial(instrument: &str, index: usize) -> Option<Partial> {
+    let mut partials = overtone_table(instrument).to_vec();
+    let removed = (index < partials.len()).then(|| partials.remove(index));
+    update(instrument, partials);
+    removed
+}
+
+// Keep the source of an edited table, so saving over the CSV reloads it again
+fn update(instrument: &str, partials: Vec<Partial>) {
+    overtones()
+        .write()
+        .unwrap()
+        .entry(instrument.to_string())
+        .and_modify(|entry| entry.table = Arc::new(partials.clone()))
+        .or_insert_with(|| Entry { table: Arc::new(partials), source: None, error: None });
+}
+
+// Load an instrument's partials from a CSV with a header row, then one
+// "ratio,amplitude" row per partial
+pub fn load_overtones(instrument: &str, path: &str) -> io::Result<()> {
+    let modified = std::fs::metadata(path)?.modified().ok();
+    let partials = parse_overtones(&std::fs::read_to_string(path)?)?;
+
+    let source = Some(Source { path: path.to_string(), modified });
+    overtones().write().unwrap().insert(instrument.to_string(), Entry { table: Arc::new(partials), source, error: None });
+    Ok(())
+}
+
+fn parse_overtones(csv: &str) -> io::Result<Vec<Partial>> {
+    let mut partials = Vec::new();
+    for line in csv.lines().skip(1) {
+        let mut parts = line.split(',').map(str::trim);
+        let parse = |part: Option<&str>| part.and_then(|part| part.parse::<f32>().ok());
+        match (parse(parts.next()), parse(parts.next())) {
+            (Some(ratio), Some(amplitude)) => partials.push(Partial { ratio, amplitude }),
+            _ if line.trim().is_empty() => {}
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad overtone row: {}", line))),
+        }
+    }
+    Ok(partials)
+}
+
+// Why overtone CSVs in use couldn't be loaded, one message per file. Their instruments
+// play the built-in tables instead.
+pub fn overtone_errors() -> Vec<String> {
+    overtones().read().unwrap().values().filter_map(|entry| entry.error.clone()).collect()
+}
+
+// Reload every table whose CSV changed on disk since it was loaded, returning the
+// instruments that were reloaded. A CSV that fails to load keeps the old table.
+pub fn reload_changed_overtones() -> Vec<String> {
+    let changed: Vec<(String, String)> = overtones()
+        .read()
+        .unwrap()
+        .iter()
+        .filter_map(|(instrument, entry)| {
+            let source = entry.source.as_ref()?;
+            let modified = std::fs::metadata(&source.path).ok()?.modified().ok();
+            (modified != source.modified).then(|| (instrument.clone(), source.path.clone()))
+        })
+        .collect();
+
+    changed
+        .into_iter()
+        .filter(|(instrument, path)| load_overtones(instrument, path).is_ok())
+        .map(|(instrument, _)| instrument)
+        .collect()
+}
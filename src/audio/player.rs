@@ -1,10 +1,272 @@
-use rodio::{OutputStream, Source, buffer::SamplesBuffer};
+use rodio::{Decoder, OutputStream, Sample, Source, buffer::SamplesBuffer};
+use rodio::decoder::DecoderError;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
 use std::time::Duration;
+use crate::song::{Beats, Marker, Song, TempoMap};
+use super::transport::Transport;
+use super::waveform::{render_song, CHANNELS};
 
+/// Play a mono waveform on the default output device, blocking for `duration` seconds.
+///
+/// An invalid duration falls back to the length of the waveform. Panics if there is no
+/// audio device.
 pub fn play_waveform(waveform: Vec<f32>, sample_rate: u32, duration: f32) {
+    play_interleaved(waveform, 1, sample_rate, duration);
+}
+
+/// The same as [`play_waveform`] for interleaved frames of `channels` samples, e.g. a
+/// stereo render.
+pub fn play_interleaved(waveform: Vec<f32>, channels: u16, sample_rate: u32, duration: f32) {
+    // Nothing to play for empty songs
+    if waveform.is_empty() || channels == 0 || sample_rate == 0 {
+        return;
+    }
+
+    // Don't trust a duration that doesn't make sense, fall back to the buffer length
+    let duration = if duration.is_finite() && duration >= 0.0 {
+        duration
+    } else {
+        (waveform.len() / channels as usize) as f32 / sample_rate as f32
+    };
+
+    let source = SamplesBuffer::new(channels, sample_rate, waveform);
+    play_source(source, duration);
+}
+
+#[derive(Debug)]
+pub enum PlayFileError {
+    Open(io::Error),
+    Decode(DecoderError),
+}
+
+impl fmt::Display for PlayFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlayFileError::Open(error) => write!(f, "could not open: {}", error),
+            PlayFileError::Decode(error) => write!(f, "could not decode: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PlayFileError {}
+
+impl From<io::Error> for PlayFileError {
+    fn from(error: io::Error) -> Self {
+        PlayFileError::Open(error)
+    }
+}
+
+impl From<DecoderError> for PlayFileError {
+    fn from(error: DecoderError) -> Self {
+        PlayFileError::Decode(error)
+    }
+}
+
+// Decode and play an audio file (WAV, FLAC, Ogg Vorbis or MP3), e.g. a previous render
+pub fn play_file(filename: &str) -> Result<(), PlayFileError> {
+    let file = File::open(filename)?;
+    let source = Decoder::new(BufReader::new(file))?;
+    let channels = source.channels() as usize;
+    let sample_rate = source.sample_rate();
+
+    // Decode up front so the exact length is known, whatever the format reports
+    let samples: Vec<i16> = source.collect();
+    if samples.is_empty() || channels == 0 || sample_rate == 0 {
+        return Ok(());
+    }
+    let duration = (samples.len() / channels) as f32 / sample_rate as f32;
+
+    play_source(SamplesBuffer::new(channels as u16, sample_rate, samples), duration);
+    Ok(())
+}
+
+// Play a source on the default output device and block until it has finished
+fn play_source<S>(source: S, duration: f32)
+where
+    S: Source + Send + 'static,
+    S::Item: Sample + Send,
+{
     let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let source = SamplesBuffer::new(1, sample_rate, waveform);
     stream_handle.play_raw(source.convert_samples()).unwrap();
 
     std::thread::sleep(Duration::from_secs((duration + 1f32) as u64));
 }
+
+// Something that happened during playback, for code that wants to follow along
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PlayerEvent {
+    Beat(u64),  // beat number, from 0
+    Bar(u64),   // bar number, from 0, sent right after the bar's first beat
+    Marker(String),
+}
+
+type PlayerCallback = Box<dyn FnMut(&PlayerEvent)>;
+
+// Plays a rendered song (stereo, as the renderer makes it) and reports beats and bars as
+// they are reached
+pub struct Player {
+    waveform: Arc<Vec<f32>>,
+    transport: Transport,
+    beats_per_bar: u32,
+    markers: Vec<Marker>,
+    callbacks: Vec<PlayerCallback>,
+}
+
+impl Player {
+    pub fn new(waveform: Vec<f32>, bpm: f32, sample_rate: u32) -> Self {
+        Player::with_tempo(waveform, TempoMap::constant(bpm), sample_rate)
+    }
+
+    pub fn with_tempo(waveform: Vec<f32>, tempo: TempoMap, sample_rate: u32) -> Self {
+        Player {
+            waveform: Arc::new(waveform),
+            transport: Transport::with_tempo(tempo, sample_rate),
+            beats_per_bar: 4,
+            markers: Vec::new(),
+            callbacks: Vec::new(),
+        }
+    }
+
+    pub fn from_song(song: &Song, sample_rate: u32) -> Self {
+        let (_, waveform, _) = render_song(song, sample_rate);
+        let mut player = Player::with_tempo(waveform, song.tempo_map(), sample_rate);
+        player.set_markers(&song.markers);
+        player
+    }
+
+    pub fn set_markers(&mut self, markers: &[Marker]) {
+        self.markers = markers.to_vec();
+        self.markers.sort_by_key(|marker| marker.beat);
+    }
+
+    // Move the transport to the marker with this name, if there is one
+    pub fn seek_to_marker(&mut self, name: &str) -> bool {
+        match self.markers.iter().find(|marker| marker.name == name) {
+            Some(marker) => {
+                self.transport.seek_beats(marker.beat);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Songs don't store a time signature, so bars are 4 beats unless set here
+    pub fn set_beats_per_bar(&mut self, beats_per_bar: u32) {
+        self.beats_per_bar = beats_per_bar.max(1);
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut Transport {
+        &mut self.transport
+    }
+
+    // Called on the playing thread, so callbacks should return quickly
+    pub fn on_event(&mut self, callback: impl FnMut(&PlayerEvent) + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    // The same events as a channel, for code running on another thread
+    pub fn events(&mut self) -> Receiver<PlayerEvent> {
+        let (sender, receiver) = channel();
+        self.on_event(move |event| {
+            let _ = sender.send(event.clone());
+        });
+        receiver
+    }
+
+    // Play from the transport's position to the end of the song, blocking until done
+    pub fn play(&mut self) {
+        let sample_rate = self.transport.sample_rate();
+        let channels = CHANNELS as usize;
+        let frames = self.waveform.len() / channels;
+        let start = self.transport.position_samples();
+        if start >= frames || sample_rate == 0 {
+            return;
+        }
+
+        // Counts samples; the transport counts frames
+        let played = Arc::new(AtomicUsize::new(start * channels));
+        let source = CountingSource { waveform: self.waveform.clone(), position: played.clone(), sample_rate };
+        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+        stream_handle.play_raw(source).unwrap();
+        self.transport.play();
+
+        // First beat and marker at or after the start position
+        let mut next_beat = self.transport.samples_to_beats(start).ceil() as i64;
+        let mut next_marker = self.markers.iter().take_while(|marker| self.transport.beats_to_samples(marker.beat) < start).count();
+        loop {
+            let position = played.load(Ordering::Relaxed) / channels;
+            while self.transport.beats_to_samples(Beats::whole(next_beat)) <= position {
+                self.send(&PlayerEvent::Beat(next_beat as u64));
+                if next_beat % self.beats_per_bar as i64 == 0 {
+                    self.send(&PlayerEvent::Bar((next_beat / self.beats_per_bar as i64) as u64));
+                }
+                next_beat += 1;
+            }
+            while let Some(marker) = self.markers.get(next_marker).filter(|marker| self.transport.beats_to_samples(marker.beat) <= position) {
+                self.send(&PlayerEvent::Marker(marker.name.clone()));
+                next_marker += 1;
+            }
+            self.transport.seek_samples(position);
+
+            if position >= frames {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        self.transport.stop();
+    }
+
+    fn send(&mut self, event: &PlayerEvent) {
+        for callback in self.callbacks.iter_mut() {
+            callback(event);
+        }
+    }
+}
+
+// Plays a waveform from a position, publishing how far the output has got
+struct CountingSource {
+    waveform: Arc<Vec<f32>>,
+    position: Arc<AtomicUsize>,
+    sample_rate: u32,
+}
+
+impl Iterator for CountingSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let position = self.position.load(Ordering::Relaxed);
+        let sample = self.waveform.get(position).copied();
+        if sample.is_some() {
+            self.position.store(position + 1, Ordering::Relaxed);
+        }
+        sample
+    }
+}
+
+impl Source for CountingSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
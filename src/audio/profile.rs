@@ -0,0 +1,47 @@
+use std::fmt;
+use std::time::Duration;
+use crate::song::Instrument;
+
+// Time spent synthesizing the notes of one instrument
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstrumentCost {
+    pub instrument: Instrument,
+    pub notes: usize,
+    pub samples: usize,
+    pub time: Duration,
+}
+
+// Where the time of a render went, to find the instruments that make it slow
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderProfile {
+    pub instruments: Vec<InstrumentCost>,
+    pub total: Duration,
+}
+
+impl RenderProfile {
+    pub(crate) fn add_note(&mut self, instrument: &Instrument, samples: usize, time: Duration) {
+        match self.instruments.iter_mut().find(|cost| cost.instrument == *instrument) {
+            Some(cost) => {
+                cost.notes += 1;
+                cost.samples += samples;
+                cost.time += time;
+            }
+            None => self.instruments.push(InstrumentCost { instrument: instrument.clone(), notes: 1, samples, time }),
+        }
+    }
+}
+
+// A table of the instruments, most expensive first
+impl fmt::Display for RenderProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut instruments: Vec<&InstrumentCost> = self.instruments.iter().collect();
+        instruments.sort_by_key(|cost| std::cmp::Reverse(cost.time));
+
+        writeln!(f, "{:<16} {:>7} {:>12} {:>10} {:>7}", "Instrument", "Notes", "Samples", "Time (ms)", "Share")?;
+        for cost in instruments {
+            let share = cost.time.as_secs_f64() / self.total.as_secs_f64().max(f64::MIN_POSITIVE) * 100.0;
+            writeln!(f, "{:<16} {:>7} {:>12} {:>10.1} {:>6.1}%", cost.instrument.to_string(), cost.notes, cost.samples, cost.time.as_secs_f64() * 1000.0, share)?;
+        }
+        write!(f, "{:<16} {:>7} {:>12} {:>10.1}", "Total", "", "", self.total.as_secs_f64() * 1000.0)
+    }
+}
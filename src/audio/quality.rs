@@ -0,0 +1,34 @@
+use super::overtones::{Partial, OvertoneTable};
+use std::sync::Arc;
+
+// How much detail a render goes for. Previews are for fast feedback, e.g. while
+// watching a folder; final renders are for exports.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum RenderQuality {
+    Preview,  // additive instruments play only their strongest partials, levels are measured on samples only
+    #[default]
+    Final,
+}
+
+// Partials an additive instrument keeps in previews
+const PREVIEW_PARTIALS: usize = 32;
+
+impl RenderQuality {
+    // The partials of an overtone table that are played at this quality
+    pub(crate) fn partials(&self, table: OvertoneTable) -> OvertoneTable {
+        match self {
+            RenderQuality::Preview if table.len() > PREVIEW_PARTIALS => {
+                let mut partials: Vec<Partial> = table.to_vec();
+                partials.sort_by(|a, b| b.amplitude.abs().total_cmp(&a.amplitude.abs()));
+                partials.truncate(PREVIEW_PARTIALS);
+                Arc::new(partials)
+            }
+            _ => table,
+        }
+    }
+
+    // Whether normalization looks for peaks between samples, which takes oversampling
+    pub(crate) fn true_peak(&self) -> bool {
+        *self == RenderQuality::Final
+    }
+}
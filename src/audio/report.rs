@@ -0,0 +1,117 @@
+use serde::Serialize;
+use crate::song::{Beats, Instrument, MAX_SONG_SECONDS};
+use crate::units::note_name;
+
+// A note the render had to leave out or alter
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReportedNote {
+    pub instrument: Instrument,
+    pub pitch: u8,
+    pub beat: Beats,
+}
+
+// A note whose tail ran past the end of the song, with the number of samples cut off
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TruncatedTail {
+    pub note: ReportedNote,
+    pub samples: usize,
+}
+
+// A run of frames over full scale, from start up to (not including) end
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ClippedRegion {
+    pub start: usize,
+    pub end: usize,
+    pub peak: f32,
+}
+
+// Everything a render skipped or changed, so data loss doesn't go unnoticed
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct RenderReport {
+    // Notes that never got an Off and weren't played
+    pub missing_offs: Vec<ReportedNote>,
+    pub truncated_tails: Vec<TruncatedTail>,
+    // Only possible with a frozen gain, automatic normalization keeps the render below full scale
+    pub clipped_regions: Vec<ClippedRegion>,
+    // Notes outside their instrument's range, played anyway
+    pub out_of_range: Vec<ReportedNote>,
+    // Custom effects the song uses that aren't registered, left out of the mix
+    pub missing_effects: Vec<String>,
+    // Overtone CSVs that couldn't be loaded, the built-in tables played instead
+    pub overtone_errors: Vec<String>,
+    // The length in seconds of a song longer than song::MAX_SONG_SECONDS, rendered as silence
+    pub too_long: Option<f32>,
+}
+
+impl RenderReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing_offs.is_empty() && self.truncated_tails.is_empty() && self.clipped_regions.is_empty() && self.out_of_range.is_empty() && self.missing_effects.is_empty() && self.overtone_errors.is_empty() && self.too_long.is_none()
+    }
+
+    // One line per kind of problem, in the style of the song warnings
+    pub fn summary(&self, sample_rate: u32) -> Vec<String> {
+        let describe = |note: &ReportedNote| format!("{} {} at beat {}", note.instrument, note_name(note.pitch), note.beat);
+        let mut lines = Vec::new();
+
+        if let Some(seconds) = self.too_long {
+            lines.push(format!("rendered nothing, the song is {} s long and renders are limited to {} s", seconds, MAX_SONG_SECONDS));
+        }
+
+        if let Some(first) = self.missing_offs.first() {
+            lines.push(format!("skipped {} notes without an Off, e.g. {}", self.missing_offs.len(), describe(first)));
+        }
+        if let Some(first) = self.truncated_tails.first() {
+            let samples: usize = self.truncated_tails.iter().map(|tail| tail.samples).sum();
+            lines.push(format!(
+                "cut the tails of {} notes at the end of the song ({:.2} s in total), e.g. {}",
+                self.truncated_tails.len(),
+                samples as f32 / sample_rate as f32,
+                describe(&first.note)
+            ));
+        }
+        if !self.clipped_regions.is_empty() {
+            let frames: usize = self.clipped_regions.iter().map(|region| region.end - region.start).sum();
+            let peak = self.clipped_regions.iter().map(|region| region.peak).fold(0.0, f32::max);
+            lines.push(format!(
+                "{} frames in {} regions clip, peaking at {:.2}, e.g. from {:.2} s",
+                frames,
+                self.clipped_regions.len(),
+                peak,
+                self.clipped_regions[0].start as f32 / sample_rate as f32
+            ));
+        }
+        if let Some(first) = self.out_of_range.first() {
+            lines.push(format!("played {} notes outside their instrument's range, e.g. {}", self.out_of_range.len(), describe(first)));
+        }
+        if !self.missing_effects.is_empty() {
+            lines.push(format!("left out the effects {}, which aren't registered", self.missing_effects.join(", ")));
+        }
+        for error in &self.overtone_errors {
+            lines.push(format!("played the built-in overtones, could not load {}", error));
+        }
+
+        lines
+    }
+}
+
+// Find the runs of frames with a sample over full scale, in interleaved frames of
+// `channels` samples
+pub(crate) fn clipped_regions(waveform: &[f32], channels: u16) -> Vec<ClippedRegion> {
+    let mut regions: Vec<ClippedRegion> = Vec::new();
+
+    for (index, frame) in waveform.chunks(channels.max(1) as usize).enumerate() {
+        let level = frame.iter().fold(0.0f32, |level, sample| level.max(sample.abs()));
+        if level <= 1.0 {
+            continue;
+        }
+        match regions.last_mut() {
+            Some(region) if region.end == index => {
+                region.end = index + 1;
+                region.peak = region.peak.max(level);
+            }
+            _ => regions.push(ClippedRegion { start: index, end: index + 1, peak: level }),
+        }
+    }
+
+    regions
+}
@@ -0,0 +1,96 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::utils::{to_s16le, streaming_wav_header};
+
+// How far ahead of real time a listener is sent audio, to ride out network hiccups
+const BUFFER_AHEAD_SECS: f32 = 0.5;
+const BLOCK_SECS: f32 = 0.1;
+
+// Limits so that slow or misbehaving clients can't pile up threads: a client has a few
+// seconds to send its request and to take each block, and the request is capped in size
+const MAX_LISTENERS: usize = 64;
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_BYTES: u64 = 16 * 1024;
+
+// Serve a waveform of interleaved frames of `channels` samples as an endless internet
+// radio stream over HTTP: the waveform loops, and every listener hears the same point
+// of the loop, like a broadcast.
+// The stream is a 16-bit WAV sent with chunked transfer encoding. Only returns if the
+// address can't be bound. Listeners beyond MAX_LISTENERS are turned away.
+pub fn serve_stream(waveform: Vec<f32>, channels: u16, sample_rate: u32, address: &str) -> io::Result<()> {
+    if waveform.len() < channels.max(1) as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "nothing to stream"));
+    }
+    let listener = TcpListener::bind(address)?;
+    let waveform = Arc::new(waveform);
+    let start = Instant::now();
+    let listeners = Arc::new(AtomicUsize::new(0));
+
+    for mut client in listener.incoming().flatten() {
+        if listeners.fetch_add(1, Ordering::SeqCst) >= MAX_LISTENERS {
+            listeners.fetch_sub(1, Ordering::SeqCst);
+            let _ = client.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n");
+            continue;
+        }
+        let waveform = Arc::clone(&waveform);
+        let listeners = Arc::clone(&listeners);
+        // A listener that goes away just ends its thread
+        thread::spawn(move || {
+            let _ = stream_to_client(client, &waveform, channels.max(1), sample_rate, start);
+            listeners.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+fn stream_to_client(mut client: TcpStream, waveform: &[f32], channels: u16, sample_rate: u32, start: Instant) -> io::Result<()> {
+    client.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    client.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    // Whatever was asked for, the answer is the stream; just consume the request headers
+    let mut reader = BufReader::new(client.try_clone()?.take(MAX_REQUEST_BYTES));
+    let mut line = String::new();
+    loop {
+        match reader.read_line(&mut line)? {
+            // The client hung up, or sent more than a request's worth without ending it
+            0 => return Err(io::Error::new(io::ErrorKind::InvalidData, "incomplete request")),
+            1 | 2 => break,
+            _ => line.clear(),
+        }
+    }
+
+    client.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: audio/wav\r\nCache-Control: no-cache\r\nTransfer-Encoding: chunked\r\n\r\n")?;
+    write_chunk(&mut client, &streaming_wav_header(sample_rate, channels))?;
+
+    // Positions count frames, a trailing partial frame is never sent
+    let channels = channels as usize;
+    let frames = waveform.len() / channels;
+    let live_position = |elapsed: Duration| (elapsed.as_secs_f32() * sample_rate as f32) as usize;
+    let block_frames = ((BLOCK_SECS * sample_rate as f32) as usize).max(1);
+    let ahead_frames = (BUFFER_AHEAD_SECS * sample_rate as f32) as usize;
+    let mut sent = live_position(start.elapsed());
+
+    loop {
+        while sent < live_position(start.elapsed()) + ahead_frames {
+            let block: Vec<f32> = (sent..sent + block_frames)
+                .flat_map(|frame| {
+                    let start = frame % frames * channels;
+                    waveform[start..start + channels].iter().copied()
+                })
+                .collect();
+            write_chunk(&mut client, &to_s16le(&block))?;
+            sent += block_frames;
+        }
+        thread::sleep(Duration::from_secs_f32(BLOCK_SECS / 2.0));
+    }
+}
+
+fn write_chunk(client: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+    write!(client, "{:x}\r\n", data.len())?;
+    client.write_all(data)?;
+    client.write_all(b"\r\n")
+}
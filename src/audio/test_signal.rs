@@ -0,0 +1,81 @@
+use crate::units::{db_to_linear, seconds_to_samples};
+use crate::utils::random_bipolar;
+
+use std::f32::consts::PI;
+
+// Calibration signals for checking playback chains and measuring processing
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestSignal {
+    Sine { frequency: f32 },
+    Sweep { from: f32, to: f32 },  // exponential sine sweep
+    WhiteNoise,
+    PinkNoise,
+    Impulse,
+}
+
+// Render a test signal whose peak is at `level_db` dBFS (noise: its RMS)
+pub fn generate_test_signal(signal: &TestSignal, duration_secs: f32, level_db: f32, sample_rate: u32) -> Vec<f32> {
+    // A sweep that stays at one frequency is a sine, the sweep's phase would divide by zero
+    if let TestSignal::Sweep { from, to } = signal {
+        if from == to {
+            return generate_test_signal(&TestSignal::Sine { frequency: *from }, duration_secs, level_db, sample_rate);
+        }
+    }
+    let sample_amount = seconds_to_samples(duration_secs, sample_rate);
+    let level = db_to_linear(level_db);
+    let sample_rate = sample_rate as f32;
+
+    match signal {
+        TestSignal::Sine { frequency } => (0..sample_amount)
+            .map(|t| level * (2.0 * PI * frequency * t as f32 / sample_rate).sin())
+            .collect(),
+        TestSignal::Sweep { from, to } => {
+            // Phase of a sweep whose frequency rises exponentially from `from` to `to`
+            let rate = (to / from).ln();
+            (0..sample_amount)
+                .map(|t| {
+                    let time = t as f32 / sample_rate;
+                    let phase = 2.0 * PI * from * duration_secs / rate * ((time / duration_secs * rate).exp() - 1.0);
+                    level * phase.sin()
+                })
+                .collect()
+        }
+        // Uniform noise has an RMS of 1/sqrt(3) of its peak
+        TestSignal::WhiteNoise => (0..sample_amount)
+            .map(|t| level * 3.0f32.sqrt() * random_bipolar(0, t as u64))
+            .collect(),
+        TestSignal::PinkNoise => pink_noise(sample_amount, level),
+        TestSignal::Impulse => {
+            let mut samples = vec![0.0; sample_amount.max(1)];
+            samples[0] = level;
+            samples
+        }
+    }
+}
+
+// White noise through Paul Kellet's -3 dB/octave filter, scaled to the requested RMS
+fn pink_noise(sample_amount: usize, level: f32) -> Vec<f32> {
+    let mut b = [0.0f32; 7];
+    let mut samples: Vec<f32> = (0..sample_amount)
+        .map(|t| {
+            let white = random_bipolar(0, t as u64);
+            b[0] = 0.99886 * b[0] + white * 0.0555179;
+            b[1] = 0.99332 * b[1] + white * 0.0750759;
+            b[2] = 0.96900 * b[2] + white * 0.153852;
+            b[3] = 0.86650 * b[3] + white * 0.3104856;
+            b[4] = 0.55000 * b[4] + white * 0.5329522;
+            b[5] = -0.7616 * b[5] - white * 0.0168980;
+            let pink = b.iter().sum::<f32>() + white * 0.5362;
+            b[6] = white * 0.115926;
+            pink
+        })
+        .collect();
+
+    let rms = (samples.iter().map(|sample| sample * sample).sum::<f32>() / sample_amount.max(1) as f32).sqrt();
+    if rms > 0.0 {
+        for sample in samples.iter_mut() {
+            *sample *= level / rms;
+        }
+    }
+    samples
+}
@@ -0,0 +1,126 @@
+use crate::song::{Beats, TempoMap};
+use crate::units::samples_to_seconds;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PlayState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+// The single source of timing for rendering and playback: where we are, how beats
+// map to samples, which region loops and whether time is moving.
+// Positions are kept in samples so they can't drift; beats and seconds are derived.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transport {
+    tempo: TempoMap,
+    sample_rate: u32,
+    position: usize,
+    loop_region: Option<(usize, usize)>,  // start and end sample, end exclusive
+    state: PlayState,
+}
+
+impl Transport {
+    pub fn new(bpm: f32, sample_rate: u32) -> Self {
+        Transport::with_tempo(TempoMap::constant(bpm), sample_rate)
+    }
+
+    pub fn with_tempo(tempo: TempoMap, sample_rate: u32) -> Self {
+        Transport { tempo, sample_rate, position: 0, loop_region: None, state: PlayState::Stopped }
+    }
+
+    // The tempo at the start
+    pub fn bpm(&self) -> f32 {
+        self.tempo.bpm()
+    }
+
+    pub fn tempo(&self) -> &TempoMap {
+        &self.tempo
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn state(&self) -> PlayState {
+        self.state
+    }
+
+    pub fn play(&mut self) {
+        self.state = PlayState::Playing;
+    }
+
+    pub fn pause(&mut self) {
+        if self.state == PlayState::Playing {
+            self.state = PlayState::Paused;
+        }
+    }
+
+    // Stopping also returns to the start
+    pub fn stop(&mut self) {
+        self.state = PlayState::Stopped;
+        self.position = 0;
+    }
+
+    // Sample index of an exact beat position. Converting the running position instead of
+    // each delta keeps rounding errors from adding up over the course of a song.
+    pub fn beats_to_samples(&self, beats: Beats) -> usize {
+        (self.tempo.seconds_at(beats) * self.sample_rate as f64) as usize
+    }
+
+    pub fn samples_to_beats(&self, samples: usize) -> f64 {
+        self.tempo.beats_at(samples as f64 / self.sample_rate as f64)
+    }
+
+    pub fn position_samples(&self) -> usize {
+        self.position
+    }
+
+    pub fn position_seconds(&self) -> f32 {
+        samples_to_seconds(self.position, self.sample_rate)
+    }
+
+    pub fn position_beats(&self) -> f64 {
+        self.samples_to_beats(self.position)
+    }
+
+    pub fn seek_samples(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    pub fn seek_beats(&mut self, position: Beats) {
+        self.position = self.beats_to_samples(position.max(Beats::ZERO));
+    }
+
+    pub fn seek_seconds(&mut self, position: f32) {
+        self.position = (position.max(0.0) * self.sample_rate as f32) as usize;
+    }
+
+    // Loop between two beat positions, or stop looping with None.
+    // An empty or reversed region doesn't loop.
+    pub fn set_loop(&mut self, region: Option<(Beats, Beats)>) {
+        self.loop_region = region
+            .map(|(start, end)| (self.beats_to_samples(start.max(Beats::ZERO)), self.beats_to_samples(end.max(Beats::ZERO))))
+            .filter(|(start, end)| start < end);
+    }
+
+    pub fn loop_region(&self) -> Option<(usize, usize)> {
+        self.loop_region
+    }
+
+    // Move forward by `samples` if playing, jumping back to the loop start each time
+    // the loop end is reached
+    pub fn advance(&mut self, samples: usize) {
+        if self.state != PlayState::Playing {
+            return;
+        }
+
+        let mut position = self.position + samples;
+        if let Some((start, end)) = self.loop_region {
+            if self.position < end && position >= end {
+                position = start + (position - end) % (end - start);
+            }
+        }
+        self.position = position;
+    }
+}
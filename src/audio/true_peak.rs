@@ -0,0 +1,46 @@
+use std::f32::consts::PI;
+
+// 4x oversampling with a 48 tap windowed-sinc interpolator, as in ITU-R BS.1770
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+// Polyphase filter coefficients, one row per interpolated position between samples
+fn interpolation_filter() -> [[f32; TAPS_PER_PHASE]; OVERSAMPLING] {
+    let length = OVERSAMPLING * TAPS_PER_PHASE;
+    let center = (length - 1) as f32 / 2.0;
+
+    let mut filter = [[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+    for (index, row) in (0..length).map(|index| (index, index % OVERSAMPLING)) {
+        let x = (index as f32 - center) / OVERSAMPLING as f32;
+        let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+        let window = 0.5 - 0.5 * (2.0 * PI * index as f32 / (length - 1) as f32).cos();
+        filter[row][index / OVERSAMPLING] = sinc * window;
+    }
+    filter
+}
+
+// Highest absolute level of the signal between samples as well as on them, which is
+// what a DAC or a lossy encoder's decoder will actually produce. Linear, 1.0 is 0 dBTP.
+// `waveform` holds interleaved frames of `channels` samples; the highest channel counts.
+pub fn true_peak(waveform: &[f32], channels: u16) -> f32 {
+    let filter = interpolation_filter();
+    let channels = channels.max(1) as usize;
+
+    (0..channels)
+        .map(|channel| {
+            let samples: Vec<f32> = waveform.iter().skip(channel).step_by(channels).copied().collect();
+            channel_peak(&samples, &filter)
+        })
+        .fold(0.0, f32::max)
+}
+
+fn channel_peak(samples: &[f32], filter: &[[f32; TAPS_PER_PHASE]; OVERSAMPLING]) -> f32 {
+    let mut peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    for window in samples.windows(TAPS_PER_PHASE) {
+        for row in filter {
+            let value: f32 = window.iter().rev().zip(row).map(|(sample, coefficient)| sample * coefficient).sum();
+            peak = peak.max(value.abs());
+        }
+    }
+    peak
+}
@@ -0,0 +1,251 @@
+use crate::song::{MidiPacket, Instrument, Articulation, Envelope};
+use crate::song::{registered_instrument, InstrumentRenderer};
+use crate::units::{midi_to_frequency, samples_to_seconds, seconds_to_samples};
+use crate::utils::random_bipolar;
+use super::modulation::Modulation;
+use super::overtones::{Partial, OvertoneTable, overtone_table};
+use super::quality::RenderQuality;
+use super::envelope::{default_envelope, envelope_level};
+
+use std::borrow::Cow;
+use std::f32::consts::PI;
+
+// Generate the piano sample by dynamically scaling the relative frequencies
+fn generate_piano_sample(overtones: &[Partial], base_frequency: f32, time: f32, phase: f32) -> f32 {
+    let base_decay_rate = -0.00015;          // Negative base decay rate
+
+    let mut piano_note = 0.0;
+
+    for &Partial { ratio, amplitude: amp } in overtones {
+        let freq = ratio * base_frequency;
+
+        // Higher partials decay faster. Time is in seconds, so the decay doesn't depend
+        // on the sample rate.
+        let decayed_amplitude = (2.0 * PI * base_decay_rate * freq * (time * time)).exp();
+
+        // Add the sine wave with the decayed amplitude to the overall piano note
+        piano_note += amp * decayed_amplitude * (2.0 * PI * (freq * time + phase)).sin();
+    }
+
+    piano_note  // Return the accumulated sample
+}
+
+fn find_custom_renderer(instrument: &Instrument) -> Option<InstrumentRenderer> {
+    match instrument {
+        Instrument::Custom(name) => registered_instrument(name),
+        _ => None,
+    }
+}
+
+// One sample of an instrument's waveform, `time` seconds into the note
+fn oscillator(instrument: &Instrument, custom_renderer: Option<&InstrumentRenderer>, overtones: &[Partial], frequency: f32, time: f32, phase: f32) -> f32 {
+    let cycles = frequency * time + phase;
+
+    match instrument {
+        Instrument::Sine => (2.0 * PI * cycles).sin(),
+        Instrument::Square => if (2.0 * PI * cycles).sin() > 0.0 { 1.0 } else { -1.0 },
+        Instrument::Triangle => 2.0 / PI * (2.0 * PI * cycles).sin().asin(),
+        Instrument::Saw => 2.0 * (cycles % 1.0) - 1.0,
+        Instrument::Piano => generate_piano_sample(overtones, frequency, time, phase),
+        // An instrument unregistered since the song was loaded renders silence
+        Instrument::Custom(_) => custom_renderer.map_or(0.0, |render| render(frequency, time + phase / frequency)),
+    }
+}
+
+// A note that has decayed to silence ends early. Single zero samples are normal in
+// any waveform, so it has to stay silent this long.
+const SILENT_TAIL_SECS: f32 = 0.02;
+
+// How much of its written length a staccato note sounds, and how quickly it is damped
+pub(super) const STACCATO_LENGTH: f32 = 0.5;
+const STACCATO_RELEASE_SECS: f32 = 0.005;
+// Accented notes are louder and start even louder, settling over the attack time
+pub(super) const ACCENT_VELOCITY: f32 = 1.3;
+const ACCENT_ATTACK_GAIN: f32 = 1.5;
+const ACCENT_ATTACK_SECS: f32 = 0.05;
+
+// How a single note is played, besides what its packet says
+#[derive(Clone)]
+pub struct NoteSettings<'a> {
+    pub start_time: f32,  // seconds into the song
+    pub detune_cents: f32,
+    pub phase: f32,       // where the oscillator starts its cycle, 0 to 1
+    pub modulation: Cow<'a, Modulation>,
+    pub quality: RenderQuality,
+    pub envelope: Option<Envelope>,  // None for the instrument's default
+}
+
+// Render a single note
+pub fn generate_waveform(packet: &MidiPacket, sample_amount: usize, sample_rate: u32, note: &NoteSettings) -> Vec<f32> {
+    let mut voice = Voice::new(packet, sample_amount, sample_rate, note.clone());
+    let mut samples = vec![0.0; voice.length];
+    voice.render(&mut samples);
+    samples.truncate(voice.played());
+    samples
+}
+
+// A sounding note, rendered a block at a time. Everything that is the same for every
+// sample is looked up when the note starts.
+pub(crate) struct Voice<'a> {
+    instrument: Instrument,
+    frequency: f32,
+    amplitude: f32,
+    phase: f32,
+    start_time: f32,
+    sample_rate: u32,
+    modulation: Cow<'a, Modulation>,
+    envelope: Envelope,
+    held_secs: f32,
+    length: usize,  // samples until the envelope has released
+    custom_renderer: Option<InstrumentRenderer>,
+    morph_target: Option<Instrument>,
+    morph_renderer: Option<InstrumentRenderer>,
+    overtones: OvertoneTable,
+    morph_overtones: Option<OvertoneTable>,
+    // Oscillator time, warped by pitch modulation so the phase stays continuous
+    phase_time: f32,
+    position: usize,
+    silent_samples: usize,
+    silent_tail: usize,
+    silent_cut: usize,  // silence played before the note was found to have ended
+    finished: bool,
+    noise: Option<(u64, f32)>,         // seed and level of a noise floor under the note
+    staccato: Option<(usize, usize)>,  // where the note is cut off, and how many samples it is damped over
+    accent_attack: usize,              // samples of harder attack, 0 without an accent
+}
+
+impl<'a> Voice<'a> {
+    pub(crate) fn new(packet: &MidiPacket, sample_amount: usize, sample_rate: u32, note: NoteSettings<'a>) -> Self {
+        let NoteSettings { start_time, detune_cents, phase, modulation, quality, envelope } = note;
+
+        // An envelope from the song shapes the note as written; with the default one the
+        // instruments keep their own lengths
+        let (held_samples, envelope) = match envelope {
+            Some(envelope) => (sample_amount, envelope),
+            None => {
+                let held_samples = match packet.instrument {
+                    // easy reverb effect for saw wave
+                    Instrument::Saw => (sample_amount as f32 * 1.5) as usize,
+                    // no abrupt end for piano
+                    Instrument::Piano => sample_rate as usize * 4,
+                    _ => sample_amount,
+                };
+                (held_samples, default_envelope(&packet.instrument))
+            }
+        };
+
+        // Look up registered instruments once per note rather than per sample
+        let morph_target = modulation.morph_target(&packet.instrument).cloned();
+        // Same for the overtones of additive instruments
+        let morph_overtones = morph_target.as_ref().map(|target| quality.partials(overtone_table(target.name())));
+
+        Voice {
+            instrument: packet.instrument.clone(),
+            frequency: midi_to_frequency(packet.pitch as f32 + detune_cents / 100.0),
+            amplitude: packet.velocity,
+            phase,
+            start_time,
+            sample_rate,
+            envelope,
+            held_secs: samples_to_seconds(held_samples, sample_rate),
+            length: held_samples + seconds_to_samples(envelope.release, sample_rate),
+            custom_renderer: find_custom_renderer(&packet.instrument),
+            morph_renderer: morph_target.as_ref().and_then(find_custom_renderer),
+            overtones: quality.partials(overtone_table(packet.instrument.name())),
+            morph_overtones,
+            morph_target,
+            modulation,
+            phase_time: 0.0,
+            position: 0,
+            silent_samples: 0,
+            silent_tail: seconds_to_samples(SILENT_TAIL_SECS, sample_rate).max(1),
+            silent_cut: 0,
+            finished: false,
+            noise: None,
+            staccato: None,
+            accent_attack: 0,
+        }
+    }
+
+    // Add a noise floor from the given random stream
+    pub(crate) fn with_noise(self, seed: u64, level: f32) -> Self {
+        Voice { noise: Some((seed, level)), ..self }
+    }
+
+    // Damp staccato notes at `duration`, also on instruments that would ring past it,
+    // and give accented notes their harder attack
+    pub(crate) fn articulated(self, articulations: &[Articulation], duration: usize) -> Self {
+        let staccato = (articulations.contains(&Articulation::Staccato) && self.length > duration)
+            .then(|| (duration, seconds_to_samples(STACCATO_RELEASE_SECS, self.sample_rate).min(duration)));
+        let accent_attack = if articulations.contains(&Articulation::Accent) { seconds_to_samples(ACCENT_ATTACK_SECS, self.sample_rate).max(1) } else { 0 };
+        Voice { staccato, accent_attack, ..self }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Samples the note has played so far; once it is finished, its length
+    pub(crate) fn played(&self) -> usize {
+        self.position - self.silent_cut
+    }
+
+    // Add the note's next samples to `output`, returning how many it played. Fewer than
+    // fit means the note has finished.
+    pub(crate) fn render(&mut self, output: &mut [f32]) -> usize {
+        let start = self.position;
+        for output in output.iter_mut() {
+            match self.next_sample() {
+                Some(sample) => *output += sample,
+                None => break,
+            }
+        }
+        self.position - start
+    }
+
+    fn next_sample(&mut self) -> Option<f32> {
+        let end = self.staccato.map_or(self.length, |(duration, _)| duration);
+        if self.finished || self.position >= end {
+            self.finished = true;
+            return None;
+        }
+
+        let t = self.position;
+        let song_time = self.start_time + samples_to_seconds(t, self.sample_rate);
+        let time = self.phase_time;
+        self.phase_time += self.modulation.pitch_ratio(song_time) / self.sample_rate as f32;
+
+        let mut sample = oscillator(&self.instrument, self.custom_renderer.as_ref(), &self.overtones, self.frequency, time, self.phase);
+        if let Some(target) = &self.morph_target {
+            let morph = self.modulation.morph_amount(&self.instrument, song_time);
+            if morph > 0.0 {
+                let morph_overtones = self.morph_overtones.as_deref().map_or(&[][..], |table| table);
+                sample += morph * (oscillator(target, self.morph_renderer.as_ref(), morph_overtones, self.frequency, time, self.phase) - sample);
+            }
+        }
+        let level = envelope_level(&self.envelope, samples_to_seconds(t, self.sample_rate), self.held_secs);
+        let mut sample = sample * self.amplitude * level * self.modulation.amplitude_gain(song_time);
+
+        // The silence already played doesn't count towards the note's length
+        self.silent_samples = if sample == 0.0 { self.silent_samples + 1 } else { 0 };
+        if self.silent_samples >= self.silent_tail {
+            self.silent_cut = self.silent_tail - 1;
+            self.finished = true;
+            return None;
+        }
+        self.position += 1;
+
+        if let Some((seed, level)) = self.noise {
+            sample += level * random_bipolar(seed, t as u64);
+        }
+        if let Some((duration, release)) = self.staccato {
+            if t + release >= duration {
+                sample *= 1.0 - (t + release + 1 - duration) as f32 / release as f32;
+            }
+        }
+        if t < self.accent_attack {
+            sample *= ACCENT_ATTACK_GAIN + (1.0 - ACCENT_ATTACK_GAIN) * t as f32 / self.accent_attack as f32;
+        }
+        Some(sample)
+    }
+}
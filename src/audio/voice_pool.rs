@@ -0,0 +1,23 @@
+// Sample buffers for sounds that are rendered whole, like release layers, handed out and
+// taken back so a render only allocates while the pool warms up instead of once per sound
+pub(crate) struct VoicePool {
+    free: Vec<Vec<f32>>,
+}
+
+impl VoicePool {
+    // `voices` buffers that each hold `capacity` samples without growing
+    pub(crate) fn new(voices: usize, capacity: usize) -> Self {
+        VoicePool { free: (0..voices).map(|_| Vec::with_capacity(capacity)).collect() }
+    }
+
+    // An empty buffer, keeping the capacity it had the last time it was used
+    pub(crate) fn take(&mut self) -> Vec<f32> {
+        let mut buffer = self.free.pop().unwrap_or_default();
+        buffer.clear();
+        buffer
+    }
+
+    pub(crate) fn recycle(&mut self, buffer: Vec<f32>) {
+        self.free.push(buffer);
+    }
+}
@@ -1,197 +1,622 @@
 use crate::song::MidiPacket;
-use crate::song::Instrument;
 use crate::song::NoteStatus;
-
+use crate::song::Song;
+use crate::song::Variation;
+use crate::song::PhaseMode;
+use crate::song::flatten_packets;
+use crate::song::Beats;
+use crate::song::Instrument;
+use crate::song::MonoMode;
+use crate::song::Drift;
+use crate::song::{NotePairing, pair_notes};
+use crate::song::Articulation;
+use crate::song::ReleaseLayer;
+use crate::song::InstrumentEnvelope;
+use crate::song::{TempoMap, MAX_SONG_SECONDS};
+use crate::song::EffectSettings;
+use crate::utils::{random_bipolar, random_unit, load_wav};
+use crate::units::{samples_to_seconds, seconds_to_samples, db_to_linear};
+use super::modulation::Modulation;
+use super::transport::Transport;
+use super::true_peak::true_peak;
+use super::profile::RenderProfile;
+use super::report::{RenderReport, ReportedNote, TruncatedTail, clipped_regions};
+use super::overtones::{reload_changed_overtones, overtone_errors};
+use super::quality::RenderQuality;
+use super::voice::{NoteSettings, Voice, STACCATO_LENGTH, ACCENT_VELOCITY};
+use super::voice_pool::VoicePool;
+use super::effects::{Effect, EffectChain};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::f32::consts::PI;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::sync::Once;
+use std::time::{Duration, Instant};
 
+/// Renders are interleaved stereo, left sample first.
+pub const CHANNELS: u16 = 2;
 
+fn calculate_song_duration(packets: &[MidiPacket], transport: &Transport) -> (f32, usize) {
+    let song_duration_beats: Beats = packets.iter().map(|packet| packet.note_delta).sum();
+    let song_duration_sec = transport.tempo().seconds_at(song_duration_beats).max(0.0) as f32;
+    let song_duration_samples = transport.beats_to_samples(song_duration_beats);
+    (song_duration_sec, song_duration_samples)
+}
 
-static mut PROMINENT_FREQUENCIES: Option<Vec<(f32, f32)>> = None;
-static INIT: Once = Once::new();
+fn calculate_note_duration(offs: &[Option<usize>], positions: &[Beats], start_index: usize, transport: &Transport) -> Option<usize> {
+    let start_sample = transport.beats_to_samples(positions[start_index]);
+    let off_index = offs[start_index]?;
+    Some(transport.beats_to_samples(positions[off_index]).saturating_sub(start_sample))
+}
 
-// Load and preprocess the prominent frequencies only once
-fn load_prominent_frequencies() {
-    // Read the CSV file
-    let file = File::open("piano_overtones.csv").expect("Could not open file.");
-    let reader = BufReader::new(file);
+// The duration of a note once its articulations are applied: legato notes are held
+// until the instrument's next note starts, staccato notes are shortened
+fn articulated_duration(packets: &[MidiPacket], positions: &[Beats], start_index: usize, duration: usize, transport: &Transport) -> usize {
+    let packet = &packets[start_index];
+    let mut duration = duration;
+    if packet.articulations.contains(&Articulation::Legato) {
+        let next = (start_index + 1..packets.len()).find(|&next| {
+            packets[next].note_status == NoteStatus::On && packets[next].instrument == packet.instrument && positions[next] > positions[start_index]
+        });
+        if let Some(next) = next {
+            let start_sample = transport.beats_to_samples(positions[start_index]);
+            duration = duration.max(transport.beats_to_samples(positions[next]).saturating_sub(start_sample));
+        }
+    }
+    if packet.articulations.contains(&Articulation::Staccato) {
+        duration = (duration as f32 * STACCATO_LENGTH) as usize;
+    }
+    duration
+}
 
-    let mut frequencies = Vec::new();
+// Samples mixed per step; fixed-size chunks without bounds checks compile to SIMD adds
+const MIX_LANES: usize = 8;
+
+// Mix a mono note into a buffer of interleaved frames, at one gain per channel (`&[1.0]`
+// for a mono buffer). `start_index` counts frames. Returns how many samples of the note
+// didn't fit in the song.
+fn add_note_waveform(waveform: &mut [f32], note_waveform: &[f32], start_index: usize, gains: &[f32]) -> usize {
+    let channels = gains.len();
+    let start = start_index.saturating_mul(channels).min(waveform.len());
+    let frames = ((waveform.len() - start) / channels).min(note_waveform.len());
+    let (note, cut) = note_waveform.split_at(frames);
+    let output = &mut waveform[start..start + frames * channels];
+
+    match *gains {
+        [gain] => mix_mono(output, note, gain),
+        _ => {
+            for (frame, sample) in output.chunks_exact_mut(channels).zip(note) {
+                for (output, gain) in frame.iter_mut().zip(gains) {
+                    *output += sample * gain;
+                }
+            }
+        }
+    }
+    cut.len()
+}
 
-    // Skip the header row
-    for (i, line) in reader.lines().enumerate() {
-        if i == 0 { continue; }
+// Left and right gain of a note. Center keeps both sides at full level, so songs without
+// panning sound as they did in mono; panning turns the other side down.
+fn pan_gains(pan: f32) -> [f32; 2] {
+    let pan = if pan.is_nan() { 0.0 } else { pan.clamp(-1.0, 1.0) };
+    [1.0 - pan.max(0.0), 1.0 + pan.min(0.0)]
+}
 
-        let line = line.expect("Could not read line.");
-        let parts: Vec<&str> = line.split(',').collect();
-        let frequency: f32 = parts[0].parse().expect("Could not parse frequency.");
-        let amplitude: f32 = parts[1].parse().expect("Could not parse amplitude.");
+fn mix_mono(output: &mut [f32], note: &[f32], gain: f32) {
+    let mut output_chunks = output.chunks_exact_mut(MIX_LANES);
+    let mut note_chunks = note.chunks_exact(MIX_LANES);
+    for (output, note) in (&mut output_chunks).zip(&mut note_chunks) {
+        for lane in 0..MIX_LANES {
+            output[lane] += note[lane] * gain;
+        }
+    }
+    for (output, sample) in output_chunks.into_remainder().iter_mut().zip(note_chunks.remainder()) {
+        *output += sample * gain;
+    }
+}
 
-        // Store relative frequency (as a multiplier of the base frequency) and amplitude
-        frequencies.push((frequency, amplitude));
+// Notes the renderer will have to skip or play outside their range, found up front
+// so legato phrases are covered the same way as single notes
+fn check_notes(packets: &[MidiPacket], positions: &[Beats], offs: &[Option<usize>], report: &mut RenderReport) {
+    for (index, packet) in packets.iter().enumerate() {
+        if packet.note_status != NoteStatus::On {
+            continue;
+        }
+        let note = ReportedNote { instrument: packet.instrument.clone(), pitch: packet.pitch, beat: positions[index] };
+        if !packet.instrument.range().contains(&packet.pitch) {
+            report.out_of_range.push(note.clone());
+        }
+        if offs[index].is_none() {
+            report.missing_offs.push(note);
+        }
     }
+}
+
+// Highest true peak a render may have. Lossy encoders add overshoot of their own,
+// so leave some room below full scale.
+const TRUE_PEAK_CEILING_DB: f32 = -1.0;
+
+// Scale the waveform down if its true peak (including peaks between samples) goes over
+// the ceiling, returning the gain that was applied. Previews only look at the samples.
+fn normalize_waveform(waveform: &mut [f32], quality: RenderQuality) -> f32 {
+    let ceiling = db_to_linear(TRUE_PEAK_CEILING_DB);
+    let peak = if quality.true_peak() {
+        true_peak(waveform, CHANNELS)
+    } else {
+        waveform.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+    };
+    let gain = ceiling / peak.max(ceiling);
+    apply_gain(waveform, gain);
+    gain
+}
 
-    // Store the preloaded data in a global static variable
-    unsafe {
-        PROMINENT_FREQUENCIES = Some(frequencies);
+fn apply_gain(waveform: &mut [f32], gain: f32) {
+    for sample in waveform {
+        *sample *= gain;
     }
 }
 
-// Generate the piano sample by dynamically scaling the relative frequencies
-fn generate_piano_sample(base_frequency: f32, time: f32) -> f32 {
-    let base_decay_rate = -0.00015;          // Negative base decay rate
-    let decay_threshold = 0.0005;    // Threshold below which we stop adding a frequency component
+/// Render bare packets at `bpm` to a mono waveform at `sample_rate`.
+///
+/// Returns the song's duration in seconds, the normalized samples and a report of the
+/// notes that had to be skipped or cut short. Song-level settings such as LFOs or
+/// grooves don't apply; render a [`Song`] with `render_song` for those. Panned notes
+/// are mixed down; [`generate_stereo_wave_from_packets`] keeps both sides.
+pub fn generate_wave_from_packets(packets: &[MidiPacket], bpm: f32, sample_rate: u32) -> (f32, Vec<f32>, RenderReport) {
+    let (song_duration_sec, waveform, report) = generate_stereo_wave_from_packets(packets, bpm, sample_rate);
+    (song_duration_sec, downmix(&waveform), report)
+}
 
-    // Ensure prominent frequencies are loaded only once
-    INIT.call_once(|| {
-        load_prominent_frequencies();
-    });
+/// The same as [`generate_wave_from_packets`], rendering interleaved frames of
+/// [`CHANNELS`] samples (left, then right).
+pub fn generate_stereo_wave_from_packets(packets: &[MidiPacket], bpm: f32, sample_rate: u32) -> (f32, Vec<f32>, RenderReport) {
+    let (song_duration_sec, waveform, _, _, report) = render_packets(packets, &TempoMap::constant(bpm), sample_rate, &RenderSettings::bare());
+    (song_duration_sec, waveform, report)
+}
 
-    // Retrieve the preloaded prominent frequencies
-    let prominent_frequencies = unsafe {
-        PROMINENT_FREQUENCIES.as_ref().expect("Frequency data not loaded.")
-    };
+// The average of the channels of every frame. Centered notes play at full level on
+// both sides, so they come out exactly as they were rendered.
+fn downmix(waveform: &[f32]) -> Vec<f32> {
+    waveform.chunks(CHANNELS as usize).map(|frame| frame.iter().sum::<f32>() / CHANNELS as f32).collect()
+}
 
-    let mut piano_note = 0.0;
+// Render a whole song, including its song-level settings such as LFO modulation
+pub fn generate_wave_from_song(song: &Song, sample_rate: u32) -> (f32, Vec<f32>) {
+    let (song_duration_sec, waveform, _) = render_song(song, sample_rate);
+    (song_duration_sec, waveform)
+}
 
-    // Scale time to simulate a more noticeable decay effect
-    let scaled_time = time * 1.0;  // Adjust this factor as needed
+// Same as generate_wave_from_song, also returning the normalization gain that was
+// applied: the song's frozen gain if it has one, otherwise the automatic one
+pub fn render_song(song: &Song, sample_rate: u32) -> (f32, Vec<f32>, f32) {
+    let (song_duration_sec, waveform, gain, _) = render_song_profiled(song, sample_rate);
+    (song_duration_sec, waveform, gain)
+}
 
-    for &(relative_freq, amp) in prominent_frequencies.iter() {
-        let freq = relative_freq * base_frequency;
+// Same as render_song, also returning how long each instrument took to synthesize
+pub fn render_song_profiled(song: &Song, sample_rate: u32) -> (f32, Vec<f32>, f32, RenderProfile) {
+    let (song_duration_sec, waveform, gain, profile, _) = render_song_with_report(song, sample_rate, RenderQuality::Final);
+    (song_duration_sec, waveform, gain, profile)
+}
 
-        // Adjust decay rate: base rate plus additional decay for higher frequencies
-        // Calculate the decayed amplitude
-        let decayed_amplitude = (2.0 * PI * base_decay_rate * freq * (scaled_time * scaled_time)).exp();
+// Same as render_song_profiled at the given quality, also reporting what the render
+// skipped or cut short
+pub fn render_song_with_report(song: &Song, sample_rate: u32, quality: RenderQuality) -> (f32, Vec<f32>, f32, RenderProfile, RenderReport) {
+    // Pick up overtone tables edited since the last render
+    reload_changed_overtones();
+    let tempo = song.tempo_map();
+    let settings = RenderSettings {
+        modulation: Modulation::new(&song.lfos, &song.modulations, &song.morphs, &tempo, song.seed),
+        variations: &song.variations,
+        mono: &song.mono,
+        drifts: &song.drifts,
+        pairing: song.pairing,
+        releases: release_sounds(&song.releases, sample_rate),
+        envelopes: &song.envelopes,
+        effects: &song.effects,
+        frozen_gain: song.normalization_gain,
+        quality,
+    };
+    let packets = flatten_packets(song);
+    render_packets(&packets, &tempo, sample_rate, &settings)
+}
 
-        // Skip this frequency if its contribution is below the threshold
-        if decayed_amplitude.abs() < decay_threshold && false{
-            break;
+// Detune (in cents) and velocity multiplier for one note trigger
+fn humanize(variations: &[Variation], packet: &MidiPacket, packet_index: usize) -> (f32, f32) {
+    match variations.iter().find(|variation| variation.instrument == packet.instrument) {
+        Some(variation) => {
+            let index = packet_index as u64 * 2;
+            let detune_cents = variation.detune_cents * random_bipolar(variation.seed, index);
+            let velocity_scale = 1.0 + variation.velocity * random_bipolar(variation.seed, index + 1);
+            (detune_cents, velocity_scale)
         }
-
-        // Add the sine wave with the decayed amplitude to the overall piano note
-        piano_note += amp * decayed_amplitude * (2.0 * PI * freq * time).sin();
+        None => (0.0, 1.0),
     }
-
-    piano_note  // Return the accumulated sample
 }
 
-pub fn generate_waveform(packet: &MidiPacket, sample_amount: usize, sample_rate: u32) -> Vec<f32> {
-    let mut samples = Vec::new();
-    let frequency = 440.0 * 2.0f32.powf((packet.pitch as f32 - 69.0) / 12.0);
-    let amplitude = packet.velocity;
+// Follow a legato voice from the note at `start_index` through every note of the same
+// instrument that starts exactly where the previous one ends, marking those as played.
+// Returns where the last note ends and the pitch steps along the way, in semitones
+// from the first note.
+fn legato_phrase(packets: &[MidiPacket], positions: &[Beats], offs: &[Option<usize>], start_index: usize, played: &mut [bool]) -> Option<(Beats, Vec<(Beats, f32)>)> {
+    let first = &packets[start_index];
+    let mut steps = Vec::new();
+    let mut current = start_index;
+
+    loop {
+        // Like the renderer, a note that never ends doesn't play
+        let Some(off_index) = offs[current] else {
+            return steps.pop().map(|(end, _)| (end, steps));
+        };
+        let end = positions[off_index];
+
+        let next = (off_index + 1..packets.len())
+            .take_while(|&index| positions[index] == end)
+            .find(|&index| packets[index].instrument == first.instrument && packets[index].note_status == NoteStatus::On);
+        match next {
+            Some(index) => {
+                played[index] = true;
+                steps.push((end, packets[index].pitch as f32 - first.pitch as f32));
+                current = index;
+            }
+            None => return Some((end, steps)),
+        }
+    }
+}
 
+// Separate random stream for start phases, so turning them on doesn't change
+// the detune and velocity rolls of an existing variation
+const PHASE_SEED: u64 = 0x0070_6861_7365;
 
-    let mut sample_amount_adjusted = sample_amount as u32;
+// Separate stream for a drifting voice's noise floor
+const NOISE_SEED: u64 = 0x006e_6f69_7365;
 
-    // easy reverb effect for saw wave
-    if  packet.instrument == Instrument::Saw {
-        sample_amount_adjusted = (sample_amount as f32 * 1.5) as u32;
-    }
+const RELEASE_SEED: u64 = 0x0072_656c_6561_7365;
+// Release noise is low-passed here, so it thumps rather than hisses
+const RELEASE_NOISE_CUTOFF: f32 = 1500.0;
 
-    // no abrupt end for piano
-    if  packet.instrument == Instrument::Piano {
-        sample_amount_adjusted = sample_rate * 4;
-    }
+// A release layer ready to play, with its sample at the render's rate if it has one
+struct ReleaseSound<'a> {
+    layer: &'a ReleaseLayer,
+    sample: Option<Vec<f32>>,
+}
 
-    for t in 0..sample_amount_adjusted {
-        let time = t as f32 / sample_rate as f32;
+fn release_sounds(layers: &[ReleaseLayer], sample_rate: u32) -> Vec<ReleaseSound<'_>> {
+    layers
+        .iter()
+        .map(|layer| {
+            // A sample that can't be loaded is a song warning, the noise plays instead
+            let sample = layer.sample.as_ref().and_then(|filename| load_wav(filename).ok());
+            ReleaseSound { layer, sample: sample.map(|(samples, rate)| resample(&samples, rate, sample_rate)) }
+        })
+        .collect()
+}
 
-        let sample = match packet.instrument {
-            Instrument::Sine => (2.0 * PI * frequency * time).sin(),
-            Instrument::Square => if (2.0 * PI * frequency * time).sin() > 0.0 { 1.0 } else { -1.0 },
-            Instrument::Triangle => (2.0 * PI * frequency * time).asin(),
-            Instrument::Saw => 2.0 * ((frequency * time) % 1.0) - 1.0,
-            Instrument::Piano => generate_piano_sample(frequency, time),
-        } * amplitude;
+// The release of one note, as loud as the note was struck. Every note gets its own
+// noise so repeated releases don't sound identical.
+fn release_waveform(sound: &ReleaseSound, velocity: f32, seed: u64, sample_rate: u32, samples: &mut Vec<f32>) {
+    let level = db_to_linear(sound.layer.level_db) * velocity;
+    samples.clear();
+    if let Some(sample) = &sound.sample {
+        samples.extend(sample.iter().map(|sample| sample * level));
+        return;
+    }
 
-        if t > 1000 && sample == 0.0 {
-            break;
-        }
+    let length = seconds_to_samples(sound.layer.length, sample_rate).max(1);
+    let smoothing = 1.0 - (-2.0 * PI * RELEASE_NOISE_CUTOFF / sample_rate as f32).exp();
+    let mut filtered = 0.0;
+    samples.extend((0..length).map(|t| {
+        filtered += smoothing * (random_bipolar(seed, t as u64) - filtered);
+        // Dies away by 60 dB over the length
+        filtered * level * (-6.9 * t as f32 / length as f32).exp()
+    }));
+}
 
-        samples.push(sample);
+// Linear interpolation is enough for short release samples
+fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || samples.is_empty() {
+        return samples.to_vec();
     }
+    let step = from_rate as f64 / to_rate as f64;
+    let length = (samples.len() as f64 / step) as usize;
+    (0..length)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let next = samples.get(index + 1).copied().unwrap_or(0.0);
+            samples[index] + fraction * (next - samples[index])
+        })
+        .collect()
+}
 
-    samples
+// Random stream of one voice of a drifting instrument, so voices wander independently
+fn voice_seed(drift: &Drift, packet_index: usize) -> u64 {
+    drift.seed ^ (packet_index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
 }
 
-fn calculate_song_duration(packets: &[MidiPacket], bpm: f32, sample_rate: u32) -> (f32, usize) {
-    let seconds_per_beat = 60.0 / bpm;
-    let song_duration_sec: f32 = packets.iter().map(|packet| packet.note_delta * seconds_per_beat).sum();
-    let song_duration_samples = (song_duration_sec * sample_rate as f32) as usize;
-    (song_duration_sec, song_duration_samples)
+// For each note-on, its rank among the notes of its instrument that start on the same beat
+// and how many of them there are. Counted once per render, per note it would be quadratic.
+fn simultaneous_ranks(packets: &[MidiPacket], positions: &[Beats]) -> Vec<(usize, usize)> {
+    let notes = || packets.iter().enumerate().filter(|(_, packet)| packet.note_status == NoteStatus::On);
+    // Few instruments start on any one beat, so each beat keeps a short list
+    let mut groups: HashMap<Beats, Vec<(&Instrument, usize)>> = HashMap::new();
+    let mut ranks = vec![(0, 0); packets.len()];
+
+    for (index, packet) in notes() {
+        let group = groups.entry(positions[index]).or_default();
+        let position = match group.iter().position(|(instrument, _)| **instrument == packet.instrument) {
+            Some(position) => position,
+            None => {
+                group.push((&packet.instrument, 0));
+                group.len() - 1
+            }
+        };
+        ranks[index].0 = group[position].1;
+        group[position].1 += 1;
+    }
+    for (index, packet) in notes() {
+        let group = &groups[&positions[index]];
+        ranks[index].1 = group.iter().find(|(instrument, _)| **instrument == packet.instrument).map_or(1, |(_, count)| *count);
+    }
+    ranks
 }
 
-fn calculate_note_duration(packets: &[MidiPacket], start_index: usize, bpm: f32, sample_rate: u32) -> Option<usize> {
-    let seconds_per_beat = 60.0 / bpm;
-    let mut note_duration_samples = 0;
+// Oscillator start phase, in cycles, for one note trigger. `ranks` are the
+// simultaneous_ranks, only needed when a variation spreads phases.
+fn start_phase(variations: &[Variation], packets: &[MidiPacket], ranks: &[(usize, usize)], packet_index: usize) -> f32 {
+    let packet = &packets[packet_index];
+    let Some(variation) = variations.iter().find(|variation| variation.instrument == packet.instrument) else {
+        return 0.0;
+    };
 
-    for next_packet in packets.iter().skip(start_index + 1) {
-        note_duration_samples += (next_packet.note_delta * seconds_per_beat * sample_rate as f32) as usize;
-        if next_packet.pitch == packets[start_index].pitch
-            && next_packet.instrument == packets[start_index].instrument
-            && next_packet.note_status == NoteStatus::Off
-        {
-            return Some(note_duration_samples);
+    match variation.phase {
+        PhaseMode::Fixed => 0.0,
+        PhaseMode::Random => random_unit(variation.seed ^ PHASE_SEED, packet_index as u64),
+        PhaseMode::Spread => {
+            let (rank, count) = ranks[packet_index];
+            rank as f32 / count.max(1) as f32
         }
     }
+}
+
+// Samples rendered at a time: every sounding voice renders a block, which is mixed into
+// the song while it is still in cache
+const BLOCK_SIZE: usize = 256;
+
+// Release layers are rendered whole when they start. They are short, so a few buffers
+// cover the ones that overlap.
+const VOICE_BUFFERS: usize = 4;
+const VOICE_BUFFER_SECS: f32 = 0.5;
 
-    None
+// Something that plays from a sample of the song on
+enum Sound<'a> {
+    Note { voice: Box<Voice<'a>>, note: ReportedNote, time: Duration },
+    Release { sound: &'a ReleaseSound<'a>, velocity: f32, seed: u64, samples: Vec<f32>, played: usize },
 }
 
-fn add_note_waveform(waveform: &mut Vec<f32>, note_waveform: &[f32], start_index: usize) {
-    for (i, sample) in note_waveform.iter().enumerate() {
-        if start_index + i >= waveform.len() {
-            break;
-        }
-        waveform[start_index + i] += sample;
-    }
+struct ScheduledSound<'a> {
+    order: usize,  // sounds are mixed in this order, so a render adds them up the same way every time
+    start: usize,
+    gains: [f32; 2],
+    sound: Sound<'a>,
 }
 
-fn normalize_waveform(waveform: &mut Vec<f32>) {
-    let max_amplitude = waveform.iter().copied().fold(0.0_f32, f32::max).max(1.0);
-    if max_amplitude > 0.0 {
-        for sample in waveform {
-            *sample /= max_amplitude;
-        }
+// The song-level settings a render applies on top of the packets
+struct RenderSettings<'a> {
+    modulation: Modulation,
+    variations: &'a [Variation],
+    mono: &'a [MonoMode],
+    drifts: &'a [Drift],
+    pairing: NotePairing,
+    releases: Vec<ReleaseSound<'a>>,
+    envelopes: &'a [InstrumentEnvelope],
+    effects: &'a [EffectSettings],
+    frozen_gain: Option<f32>,  // reused instead of normalizing
+    quality: RenderQuality,
+}
+
+impl RenderSettings<'_> {
+    // Plain packets, without anything a song adds
+    fn bare() -> Self {
+        RenderSettings { modulation: Modulation::none(), variations: &[], mono: &[], drifts: &[], pairing: NotePairing::Strict, releases: Vec::new(), envelopes: &[], effects: &[], frozen_gain: None, quality: RenderQuality::Final }
     }
 }
 
-pub fn generate_wave_from_packets(packets: &[MidiPacket], bpm: f32, sample_rate: u32) -> (f32, Vec<f32>) {
+fn render_packets(packets: &[MidiPacket], tempo: &TempoMap, sample_rate: u32, settings: &RenderSettings) -> (f32, Vec<f32>, f32, RenderProfile, RenderReport) {
+    let RenderSettings { modulation, variations, mono, drifts, pairing, releases, envelopes, effects, frozen_gain, quality } = settings;
+    let render_start = Instant::now();
+    let mut profile = RenderProfile::default();
+    let mut report = RenderReport::default();
+
+    // A tempo that can't place notes in time renders as an empty song
+    if !tempo.bpm().is_finite() || tempo.bpm() <= 0.0 || sample_rate == 0 {
+        return (0.0, Vec::new(), frozen_gain.unwrap_or(1.0), profile, report);
+    }
+
     // Calculate song duration
-    let (song_duration_sec, song_duration_samples) = calculate_song_duration(packets, bpm, sample_rate);
-    let mut waveform = vec![0.0f32; song_duration_samples];
+    let transport = Transport::with_tempo(tempo.clone(), sample_rate);
+    let (song_duration_sec, song_duration_samples) = calculate_song_duration(packets, &transport);
+    if song_duration_sec as f64 > MAX_SONG_SECONDS {
+        report.too_long = Some(song_duration_sec);
+        return (0.0, Vec::new(), frozen_gain.unwrap_or(1.0), profile, report);
+    }
+    let mut waveform = vec![0.0f32; song_duration_samples * CHANNELS as usize];
 
     // Process each packet
-    let seconds_per_beat = 60.0 / bpm;
-    let mut sample_index = 0;
+    let positions: Vec<Beats> = packets
+        .iter()
+        .scan(Beats::ZERO, |position, packet| {
+            *position += packet.note_delta;
+            Some(*position)
+        })
+        .collect();
+    let offs = pair_notes(packets, *pairing);
+    check_notes(packets, &positions, &offs, &mut report);
+    let ranks = if variations.iter().any(|variation| variation.phase == PhaseMode::Spread) {
+        simultaneous_ranks(packets, &positions)
+    } else {
+        Vec::new()
+    };
+    // Notes already played as part of a legato phrase
+    let mut played = vec![false; packets.len()];
+    let mut schedule: Vec<ScheduledSound> = Vec::new();
 
     for (packet_index, packet) in packets.iter().enumerate() {
-        sample_index += (packet.note_delta * seconds_per_beat * sample_rate as f32) as usize;
+        let position = positions[packet_index];
+        let sample_index = transport.beats_to_samples(position);
 
         // Skip if note is off or it's the last packet
-        if packet.note_status == NoteStatus::Off || packet_index == packets.len() - 1 {
+        if packet.note_status == NoteStatus::Off || packet_index == packets.len() - 1 || played[packet_index] {
             continue;
         }
 
-        // Calculate the duration of the current note
-        let note_duration_samples = match calculate_note_duration(packets, packet_index, bpm, sample_rate) {
-            Some(duration) => duration,
-            None => continue,
+        // A legato voice plays the following notes as pitch changes of this one
+        let legato = mono.iter().find(|mode| mode.instrument == packet.instrument && !mode.retrigger);
+        let (note_duration_samples, note_modulation) = match legato {
+            Some(mode) => {
+                let (end, steps) = match legato_phrase(packets, &positions, &offs, packet_index, &mut played) {
+                    Some(phrase) => phrase,
+                    None => continue,
+                };
+                let steps = steps
+                    .into_iter()
+                    .map(|(step, semitones)| (samples_to_seconds(transport.beats_to_samples(step), sample_rate), semitones))
+                    .collect();
+                let duration = transport.beats_to_samples(end).saturating_sub(sample_index);
+                (duration, Some(modulation.with_legato(steps, mode.glide)))
+            }
+            // Calculate the duration of the current note
+            None => match calculate_note_duration(&offs, &positions, packet_index, &transport) {
+                Some(duration) => (articulated_duration(packets, &positions, packet_index, duration, &transport), None),
+                None => continue,
+            },
         };
 
-        // Generate the waveform for the note
-        let note_waveform = generate_waveform(packet, note_duration_samples, sample_rate);
+        // Set up the voice that plays the note
+        let start_time = samples_to_seconds(sample_index, sample_rate);
+        let (detune_cents, velocity_scale) = humanize(variations, packet, packet_index);
+        let accent = if packet.articulations.contains(&Articulation::Accent) { ACCENT_VELOCITY } else { 1.0 };
+        let packet = MidiPacket { velocity: packet.velocity * velocity_scale * accent, ..packet.clone() };
+        let phase = start_phase(variations, packets, &ranks, packet_index);
+        let drift = drifts.iter().find(|drift| drift.instrument == packet.instrument);
+        let envelope = packet.envelope.or_else(|| envelopes.iter().find(|envelope| envelope.instrument == packet.instrument).map(|envelope| envelope.envelope));
+        let note_modulation = note_modulation.map_or(Cow::Borrowed(modulation), Cow::Owned);
+        let voice = match drift {
+            // An analog voice varies in length, wanders in pitch and has a noise floor
+            Some(drift) => {
+                let seed = voice_seed(drift, packet_index);
+                let length_scale = 1.0 + drift.length * random_bipolar(drift.seed, packet_index as u64);
+                let note_duration_samples = (note_duration_samples as f32 * length_scale) as usize;
+                let modulation = Cow::Owned(note_modulation.with_drift(drift.pitch_cents, drift.rate, seed));
+                let note = NoteSettings { start_time, detune_cents, phase, modulation, quality: *quality, envelope };
+                let voice = Voice::new(&packet, note_duration_samples, sample_rate, note);
+                match drift.noise_db {
+                    Some(noise_db) => voice.with_noise(seed ^ NOISE_SEED, db_to_linear(noise_db)),
+                    None => voice,
+                }
+            }
+            None => {
+                let note = NoteSettings { start_time, detune_cents, phase, modulation: note_modulation, quality: *quality, envelope };
+                Voice::new(&packet, note_duration_samples, sample_rate, note)
+            }
+        };
+        let voice = voice.articulated(&packet.articulations, note_duration_samples);
+        let note = ReportedNote { instrument: packet.instrument.clone(), pitch: packet.pitch, beat: position };
+        let gains = pan_gains(packet.pan);
+        schedule.push(ScheduledSound { order: packet_index * 2, start: sample_index, gains, sound: Sound::Note { voice: Box::new(voice), note, time: Duration::ZERO } });
+
+        // The release layer starts where the note is let go
+        if let Some(sound) = releases.iter().find(|release| release.layer.instrument == packet.instrument) {
+            let release = Sound::Release { sound, velocity: packet.velocity, seed: RELEASE_SEED ^ packet_index as u64, samples: Vec::new(), played: 0 };
+            schedule.push(ScheduledSound { order: packet_index * 2 + 1, start: sample_index + note_duration_samples, gains, sound: release });
+        }
+    }
 
-        // Add note waveform to the main song waveform
-        add_note_waveform(&mut waveform, &note_waveform, sample_index);
+    // Play the song a block at a time. Notes sounding past its end still play to the end,
+    // to report how much of them was cut off; release layers there are dropped.
+    schedule.sort_by_key(|scheduled| std::cmp::Reverse(scheduled.start));
+    let mut sounding: Vec<ScheduledSound> = Vec::new();
+    let mut played_notes: Vec<(usize, usize, ReportedNote, usize, Duration)> = Vec::new();
+    let mut voices = VoicePool::new(VOICE_BUFFERS, seconds_to_samples(VOICE_BUFFER_SECS, sample_rate));
+    let mut block = vec![0.0f32; BLOCK_SIZE];
+    let mut block_start = 0;
+
+    while !schedule.is_empty() || !sounding.is_empty() {
+        let block_end = block_start + BLOCK_SIZE;
+        while schedule.last().is_some_and(|scheduled| scheduled.start < block_end) {
+            let scheduled = schedule.pop().unwrap();
+            let index = sounding.partition_point(|sounding| sounding.order < scheduled.order);
+            sounding.insert(index, scheduled);
+        }
+        let channels = CHANNELS as usize;
+        let output = &mut waveform[block_start.min(song_duration_samples) * channels..block_end.min(song_duration_samples) * channels];
+
+        let mut index = 0;
+        while index < sounding.len() {
+            let scheduled = &mut sounding[index];
+            let offset = scheduled.start.saturating_sub(block_start);
+            let block = &mut block[..BLOCK_SIZE - offset];
+            let sounds_on = match &mut scheduled.sound {
+                Sound::Note { voice, time, .. } => {
+                    let note_start = Instant::now();
+                    block.fill(0.0);
+                    let rendered = voice.render(block);
+                    add_note_waveform(output, &block[..rendered], offset, &scheduled.gains);
+                    *time += note_start.elapsed();
+                    !voice.is_finished()
+                }
+                Sound::Release { .. } if output.is_empty() => false,
+                Sound::Release { sound, velocity, seed, samples, played } => {
+                    if *played == 0 {
+                        *samples = voices.take();
+                        release_waveform(sound, *velocity, *seed, sample_rate, samples);
+                    }
+                    let end = samples.len().min(*played + block.len());
+                    add_note_waveform(output, &samples[*played..end], offset, &scheduled.gains);
+                    *played = end;
+                    end < samples.len()
+                }
+            };
+            if sounds_on {
+                index += 1;
+                continue;
+            }
+            let ScheduledSound { order, start, sound, .. } = sounding.remove(index);
+            match sound {
+                Sound::Note { voice, note, time } => played_notes.push((order, start, note, voice.played(), time)),
+                Sound::Release { samples, .. } => voices.recycle(samples),
+            }
+        }
+        block_start = block_end;
     }
 
-    // Normalize the waveform
-    normalize_waveform(&mut waveform);
+    // Notes finish in any order, report them in the order of the song
+    played_notes.sort_by_key(|(order, ..)| *order);
+    for (_, start, note, samples, time) in played_notes {
+        let cut_samples = samples - samples.min(song_duration_samples.saturating_sub(start));
+        profile.add_note(&note.instrument, samples, time);
+        if cut_samples > 0 {
+            report.truncated_tails.push(TruncatedTail { note, samples: cut_samples });
+        }
+    }
 
+    // The song's effects shape the mix before it is normalized
+    let (mut effects, missing_effects) = EffectChain::from_settings(effects);
+    effects.process(&mut waveform, sample_rate);
+    report.missing_effects = missing_effects;
+    report.overtone_errors = overtone_errors();
+
+    // Normalize the waveform, or reuse a frozen gain so loudness stays the same between renders
+    let gain = match *frozen_gain {
+        Some(gain) => {
+            apply_gain(&mut waveform, gain);
+            gain
+        }
+        None => normalize_waveform(&mut waveform, *quality),
+    };
 
-    (song_duration_sec, waveform)
+    report.clipped_regions = clipped_regions(&waveform, CHANNELS);
+
+    profile.total = render_start.elapsed();
+    (song_duration_sec, waveform, gain, profile, report)
 }
@@ -0,0 +1,124 @@
+use serde::{Serialize, Deserialize};
+use crate::song::{Song, Note, Track, Key, KeyMode, detect_key, notes_from_packets, packets_from_notes};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HarmonyStyle {
+    Thirds,        // a diatonic third below the melody
+    Sixths,        // a diatonic sixth below the melody
+    Counterpoint,  // first species: one consonant note below each melody note
+}
+
+const HARMONY_TRACK: &str = "Harmony";
+
+// Harmony notes are a little quieter than the melody they follow
+const HARMONY_VELOCITY: f32 = 0.8;
+
+// Semitone intervals (within an octave) that sound consonant against the melody
+const PERFECT_CONSONANCES: [u8; 2] = [0, 7];
+const IMPERFECT_CONSONANCES: [u8; 4] = [3, 4, 8, 9];
+
+// Add a second voice below the song's melody, in the song's key.
+// The melody is the top line of the song and its tracks (see Song::extract_melody),
+// the key is detected from all of them. The new notes go in their own "Harmony" track
+// with the melody's instrument, the song's existing packets are left as they are.
+pub fn harmonize(song: &Song, style: HarmonyStyle) -> Song {
+    let melody = notes_from_packets(&song.extract_melody().packets);
+    let key = detect_key(&notes_from_packets(&song.mixed_packets())).unwrap_or(Key { tonic: 0, mode: KeyMode::Major });
+    let scale = scale_pitches(&key);
+
+    let pitches: Vec<u8> = match style {
+        HarmonyStyle::Thirds => melody.iter().map(|note| scale_step(&scale, note.pitch, -2)).collect(),
+        HarmonyStyle::Sixths => melody.iter().map(|note| scale_step(&scale, note.pitch, -5)).collect(),
+        HarmonyStyle::Counterpoint => counterpoint(&melody, &scale, &key),
+    };
+
+    let harmony: Vec<Note> = melody
+        .iter()
+        .zip(pitches)
+        .map(|(note, pitch)| Note {
+            pitch,
+            velocity: note.velocity * HARMONY_VELOCITY,
+            ..note.clone()
+        })
+        .collect();
+
+    let mut harmonized = Song { normalization_gain: None, ..song.clone() };
+    if let Some(first) = melody.first() {
+        let mut track = Track::new(HARMONY_TRACK, first.instrument.clone());
+        track.packets = packets_from_notes(&harmony);
+        harmonized.tracks.push(track);
+    }
+    harmonized
+}
+
+// Every MIDI pitch in the key's scale, ascending
+fn scale_pitches(key: &Key) -> Vec<u8> {
+    let scale = key.scale();
+    (0..=127u8).filter(|pitch| scale.contains(&(pitch % 12))).collect()
+}
+
+// Move `steps` scale degrees from a pitch; notes outside the scale count from
+// the scale note just below them
+fn scale_step(scale: &[u8], pitch: u8, steps: i32) -> u8 {
+    let index = scale.iter().rposition(|scale_pitch| *scale_pitch <= pitch).unwrap_or(0) as i32;
+    scale[(index + steps).clamp(0, scale.len() as i32 - 1) as usize]
+}
+
+// First species counterpoint below the melody: every note consonant with the melody,
+// starting and ending on a perfect consonance, no parallel fifths or octaves, and
+// preferring contrary motion and small steps. Picked greedily, note by note.
+fn counterpoint(melody: &[Note], scale: &[u8], key: &Key) -> Vec<u8> {
+    let mut voice: Vec<u8> = Vec::with_capacity(melody.len());
+
+    for (index, note) in melody.iter().enumerate() {
+        let outer = index == 0 || index == melody.len() - 1;
+        let lowest = note.pitch.saturating_sub(16);
+        let candidates = scale.iter().copied().filter(|pitch| *pitch >= lowest && *pitch + 3 <= note.pitch);
+
+        let mut best: Option<(f32, u8)> = None;
+        for pitch in candidates {
+            let interval = (note.pitch - pitch) % 12;
+            let perfect = PERFECT_CONSONANCES.contains(&interval);
+            if !perfect && !IMPERFECT_CONSONANCES.contains(&interval) {
+                continue;
+            }
+            // Start and end on a perfect consonance, ending on the tonic when possible
+            if outer && !perfect {
+                continue;
+            }
+
+            let mut cost = 0.0;
+            if index == melody.len() - 1 && pitch % 12 != key.tonic {
+                cost += 4.0;
+            }
+            if let Some(&previous) = voice.last() {
+                let previous_melody = melody[index - 1].pitch;
+                let melody_motion = note.pitch as i32 - previous_melody as i32;
+                let motion = pitch as i32 - previous as i32;
+                let previous_interval = (previous_melody - previous) % 12;
+
+                // Parallel fifths and octaves are never allowed
+                if perfect && interval == previous_interval && motion != 0 {
+                    continue;
+                }
+                if melody_motion.signum() == motion.signum() && motion != 0 {
+                    cost += 1.0;
+                }
+                cost += motion.abs() as f32 / 4.0;
+                // Perfect consonances sound empty in the middle of a phrase
+                if perfect && !outer {
+                    cost += 1.0;
+                }
+            }
+
+            if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+                best = Some((cost, pitch));
+            }
+        }
+
+        // A melody note too low for any consonance below it is doubled instead
+        voice.push(best.map_or(note.pitch, |(_, pitch)| pitch));
+    }
+
+    voice
+}
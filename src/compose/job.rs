@@ -0,0 +1,118 @@
+use serde::{Serialize, Deserialize};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use crate::song::{Song, MidiPacket, Track};
+use super::harmonize::{HarmonyStyle, harmonize};
+
+// A change applied to every song of a job before it is rendered
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Transform {
+    Transpose(i32),  // semitones, clamped to the MIDI range
+    Tempo(f32),      // replaces the song's bpm
+    Repeat(u32),     // replaces how often the song's packets are played
+    FoldOctaves,
+    ExtractMelody,
+    Harmonize(HarmonyStyle),
+}
+
+impl Transform {
+    pub fn apply(&self, song: &Song) -> Song {
+        match self {
+            Transform::Transpose(semitones) => {
+                let transpose = |packets: &[MidiPacket]| -> Vec<MidiPacket> {
+                    packets.iter().map(|packet| MidiPacket { pitch: (packet.pitch as i32 + semitones).clamp(0, 127) as u8, ..packet.clone() }).collect()
+                };
+                Song {
+                    packets: transpose(&song.packets),
+                    tracks: song.tracks.iter().map(|track| Track { packets: transpose(&track.packets), ..track.clone() }).collect(),
+                    ..song.clone()
+                }
+            }
+            Transform::Tempo(bpm) => Song { bpm: *bpm, ..song.clone() },
+            Transform::Repeat(repeat) => Song { repeat: *repeat, ..song.clone() },
+            Transform::FoldOctaves => Song { fold_octaves: true, ..song.clone() },
+            Transform::ExtractMelody => song.extract_melody(),
+            Transform::Harmonize(style) => harmonize(song, *style),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OutputFormat {
+    Wav,
+    Csv,
+    Npy,
+    Npz,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Wav => "wav",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Npy => "npy",
+            OutputFormat::Npz => "npz",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [OutputFormat::Wav, OutputFormat::Csv, OutputFormat::Npy, OutputFormat::Npz]
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+}
+
+// A reproducible batch render: which songs, what to do to them, and where the results go
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RenderJob {
+    pub songs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<Transform>,  // applied in order
+    pub formats: Vec<OutputFormat>,
+    #[serde(default = "default_destination")]
+    pub destination: String,  // directory the renders are written to
+}
+
+fn default_destination() -> String {
+    String::from(".")
+}
+
+impl RenderJob {
+    // A song with all of the job's transforms applied
+    pub fn transform(&self, song: &Song) -> Song {
+        self.transforms.iter().fold(song.clone(), |song, transform| transform.apply(&song))
+    }
+
+    // Where a song file's render in the given format goes, named after the song file
+    // (load_job refuses songs without a file name)
+    pub fn output_path(&self, song_filename: &str, format: OutputFormat) -> String {
+        let stem = Path::new(song_filename).file_stem().map_or_else(|| song_filename.into(), |stem| stem.to_string_lossy());
+        Path::new(&self.destination)
+            .join(format!("{}.{}", stem, format.extension()))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+// Load a job from a JSON file
+// Relative song paths and the destination are resolved against the job file's directory
+pub fn load_job(filename: &str) -> io::Result<RenderJob> {
+    let mut file = File::open(filename)?;
+    let mut json = String::new();
+    file.read_to_string(&mut json)?;
+    let job: RenderJob = serde_json::from_str(&json)?;
+
+    // Renders are named after the song file
+    if let Some(song) = job.songs.iter().find(|song| Path::new(song).file_stem().is_none()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("song {:?} has no file name to name its renders after", song)));
+    }
+
+    let base = Path::new(filename).parent().unwrap_or(Path::new(""));
+    let resolve = |path: &str| base.join(path).to_string_lossy().into_owned();
+    Ok(RenderJob {
+        songs: job.songs.iter().map(|song| resolve(song)).collect(),
+        destination: resolve(&job.destination),
+        ..job
+    })
+}
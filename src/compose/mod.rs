@@ -0,0 +1,5 @@
+mod harmonize;
+mod job;
+
+pub use harmonize::{HarmonyStyle, harmonize};
+pub use job::{Transform, OutputFormat, RenderJob, load_job};
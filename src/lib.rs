@@ -1,3 +1,25 @@
+//! Synthia renders songs of MIDI-like packets to audio.
+//!
+//! The items re-exported here are the stable API for using Synthia as a synthesis
+//! engine from another crate; everything else is reachable through the modules.
+//!
+//! ```no_run
+//! use synthia::{generate_wave_from_packets, play_waveform, Beats, Instrument, MidiPacket, NoteStatus};
+//!
+//! let packets = vec![
+//!     MidiPacket::new(60, Instrument::Sine, NoteStatus::On, Beats::ZERO, 0.8),
+//!     MidiPacket::new(60, Instrument::Sine, NoteStatus::Off, Beats::whole(2), 0.8),
+//! ];
+//! let (duration, waveform, _report) = generate_wave_from_packets(&packets, 120.0, 44100);
+//! play_waveform(waveform, 44100, duration);
+//! ```
+
 pub mod song;
 pub mod audio;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod units;
+pub mod plugin;
+pub mod compose;
+
+pub use song::{Song, MidiPacket, Instrument, NoteStatus, Beats};
+pub use audio::{generate_wave_from_packets, generate_stereo_wave_from_packets, play_waveform, play_interleaved, CHANNELS};
@@ -1,21 +1,969 @@
-mod song;
-mod audio;
-mod utils;
+use synthia::audio::{generate_wave_from_song, render_song, render_song_with_report, serve_stream, RenderQuality, RenderReport, CHANNELS};
+use synthia::audio::{play_interleaved, play_file, Player, true_peak, replay_gain, waveform_hash, chain_waveforms, generate_test_signal, TestSignal, compare_waveforms, used_notes, render_one_shot};
+use synthia::utils::{save_wav, read_wav_tags, load_wav, to_s16le, WavTags, BitDepth, ExportMeta, export_to_file, exporter_for, exporter_extensions};
+use synthia::plugin::load_plugins;
+use synthia::compose::load_job;
+use synthia::units::{note_name, linear_to_db};
+use synthia::song::{Song, Instrument, Beats, Marker, TempoChange, EffectSettings, notes_from_packets, analyze_song, song_warnings, importer_for, save_to_midi, load_history, save_history, History, Revision, save_to_json, PitchFormat, save_normalization_gain, load_playlist, is_playlist, song_from_template, TEMPLATES};
 
-use audio::generate_wave_from_packets;
-use audio::play_waveform;
-use utils::save_vec_to_csv;
-use song::load_from_json;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const SAMPLE_RATE: u32 = 44100;
+const PLUGIN_DIRECTORY: &str = "plugins";
 
 fn main() {
-    let filename_in: &str = "sweet_dreams.json";
-    let filename_out: &str = &(format!("{}.csv", filename_in.split('.').next().unwrap()));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Instrument plugins have to be registered before any song referencing them is loaded.
+    // Deterministic renders leave them out, as Synthia can't vouch for their output.
+    if !args.iter().any(|arg| arg == "--deterministic") {
+        for (path, result) in load_plugins(std::path::Path::new(PLUGIN_DIRECTORY)) {
+            if let Err(error) = result {
+                eprintln!("Skipping plugin {}: {}", path, error);
+            }
+        }
+    }
+
+    match args.first().map(String::as_str) {
+        Some("play") => play(&args[1..]),
+        Some("new") => new(&args[1..]),
+        Some("testsignal") => test_signal(&args[1..]),
+        Some("compare") => compare(&args[1..]),
+        Some("oneshots") => one_shots(&args[1..]),
+        Some("info") => info(&args[1..]),
+        Some("history") => history(&args[1..]),
+        Some("revert") => revert(&args[1..]),
+        Some("run") => run(&args[1..]),
+        Some("watch") => watch(&args[1..]),
+        Some("stream") => stream(&args[1..]),
+        Some("check") => check(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some(_) => render(&args),
+        None => render(&["sweet_dreams.json".to_string()]),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: synthia [song.json] [--out <file.wav|file.csv|file.npy|file.npz>] [--bits <16|32>] [--freeze-gain] [--start-at <marker>] [--deterministic] [--profile] [--quality <preview|final>] [--json]");
+    eprintln!("       synthia <song.json> --stdout-pcm [--rate <hz>] [--channels <n>] [--freeze-gain] [--deterministic] [--profile]");
+    eprintln!("       synthia play <song.json|playlist.m3u|playlist.json> [--crossfade <seconds>]");
+    eprintln!("       synthia play <audio.wav|audio.flac|audio.ogg|audio.mp3>");
+    eprintln!("       synthia new <name> [--template <{}>]", TEMPLATES.join("|"));
+    eprintln!("       synthia testsignal <sine <hz>|sweep <from hz> <to hz>|white|pink|impulse> <duration> [--out <file.wav|file.csv|file.npy|file.npz>] [--level <dBFS>] [--rate <hz>]");
+    eprintln!("       synthia compare <reference.wav> <other.wav> [--max-offset <samples>] [--threshold <dB>]");
+    eprintln!("       synthia info <song.json> [--analyze] [--json]");
+    eprintln!("       synthia history <song.json> [--add <summary>] [--no-snapshot]");
+    eprintln!("       synthia revert <song.json> <revision>");
+    eprintln!("       synthia run <job.json>");
+    eprintln!("       synthia watch <directory> [--out <directory>] [--format <wav|csv|npy|npz>] [--interval <seconds>] [--quality <preview|final>]");
+    eprintln!("       synthia stream <song.json> [--port <port>]");
+    eprintln!("       synthia check <song.json> [--quality <preview|final>] [--rate <hz>] [--max-clipping <percent>] [--json]");
+    eprintln!("       synthia convert <song.json|song.mid> <song.json|song.mid>");
+    eprintln!("       synthia oneshots <song.json> [--out <directory>] [--length <seconds>]");
+    std::process::exit(1);
+}
+
+// Load a song file, or exit with the reason it can't be loaded
+fn load_song(filename: &str) -> Song {
+    Song::load(filename).unwrap_or_else(|error| {
+        eprintln!("{}", song_load_error(filename, &error));
+        std::process::exit(1);
+    })
+}
+
+fn song_load_error(filename: &str, error: &io::Error) -> String {
+    match error.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => format!("{} is not a valid song: {}", filename, error),
+        _ => format!("could not read {}: {}", filename, error),
+    }
+}
+
+fn save_song(song: &Song, filename: &str) {
+    if let Err(error) = save_to_json(song, filename, PitchFormat::Number) {
+        eprintln!("Could not write {}: {}", filename, error);
+        std::process::exit(1);
+    }
+}
+
+fn print_warnings(song: &Song) {
+    for warning in song_warnings(song) {
+        eprintln!("Warning: {}: {}", song.songname, warning);
+    }
+}
+
+// Render a song, save it as WAV next to the input and play it
+// With --freeze-gain the normalization gain is stored in the song file for later renders
+// With --start-at playback starts from a marker instead of the beginning
+// With --out the render is written there instead, in the format of its extension
+// With --bits 16 a WAV render is saved as 16-bit PCM instead of 32-bit float
+// With --deterministic plugin instruments are refused and a hash of the output is printed
+// With --profile the time spent on each instrument is printed
+// With --quality preview the render is faster but less detailed
+// With --stdout-pcm the render is written to stdout as raw s16le PCM instead of being saved
+// and played, at the --rate and with the --channels asked for; messages go to stderr
+// With --json the summary of the render is printed as JSON (see RenderSummary)
+fn render(args: &[String]) {
+    let mut filename_in = None;
+    let mut filename_out = None;
+    let mut freeze_gain = false;
+    let mut start_at = None;
+    let mut deterministic = false;
+    let mut profile = false;
+    let mut stdout_pcm = false;
+    let mut sample_rate = None;
+    let mut channels = None;
+    let mut quality = RenderQuality::Final;
+    let mut json = false;
+    let mut bit_depth = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--freeze-gain" => freeze_gain = true,
+            "--bits" => bit_depth = Some(args.next().and_then(|value| value.parse().ok()).and_then(BitDepth::from_bits).unwrap_or_else(|| usage())),
+            "--json" => json = true,
+            "--start-at" => start_at = Some(args.next().unwrap_or_else(|| usage()).as_str()),
+            "--out" => filename_out = Some(args.next().unwrap_or_else(|| usage()).clone()),
+            "--deterministic" => deterministic = true,
+            "--profile" => profile = true,
+            "--stdout-pcm" => stdout_pcm = true,
+            "--quality" => quality = args.next().and_then(|value| parse_quality(value)).unwrap_or_else(|| usage()),
+            "--rate" => sample_rate = Some(args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage())),
+            "--channels" => channels = Some(args.next().and_then(|value| value.parse().ok()).filter(|&channels| channels > 0).unwrap_or_else(|| usage())),
+            _ if filename_in.is_none() => filename_in = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    let filename_in = filename_in.unwrap_or_else(|| usage());
+    // The rate and channel count only apply to raw output, which can't also be saved or played
+    if stdout_pcm && (filename_out.is_some() || start_at.is_some()) || !stdout_pcm && (sample_rate.is_some() || channels.is_some()) {
+        usage();
+    }
+    let sample_rate = sample_rate.unwrap_or(SAMPLE_RATE);
+    // Keep stdout clean for the audio
+    let mut log: Box<dyn Write> = if stdout_pcm { Box::new(io::stderr()) } else { Box::new(io::stdout()) };
+    let filename_out = filename_out.unwrap_or_else(|| Path::new(filename_in).with_extension("wav").to_string_lossy().into_owned());
+    if !stdout_pcm {
+        check_export_format(&filename_out);
+    }
+    // Only WAV files come in more than one bit depth
+    if bit_depth.is_some() && (stdout_pcm || !filename_out.ends_with(".wav")) {
+        usage();
+    }
+
+    // The frozen gain is written back to the song, which only works for song files
+    if freeze_gain && !filename_in.ends_with(".json") {
+        eprintln!("--freeze-gain needs a .json song to store the gain in, not {}", filename_in);
+        std::process::exit(1);
+    }
+    let mut loaded_song = load_song(filename_in);
+    if !json {
+        print_warnings(&loaded_song);
+    }
+
+    if deterministic {
+        if let Some(packet) = loaded_song.mixed_packets().iter().find(|packet| matches!(packet.instrument, Instrument::Custom(_))) {
+            eprintln!("{} uses the plugin instrument {}, which can't be rendered deterministically", filename_in, packet.instrument);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(marker) = start_at {
+        if !loaded_song.markers.iter().any(|known| known.name == marker) {
+            eprintln!("No marker named {} in {}", marker, filename_in);
+            std::process::exit(1);
+        }
+    }
+
+    if freeze_gain {
+        loaded_song.normalization_gain = None;
+    }
+    let (song_duration_secs, waveform, gain, render_profile, report) = render_song_with_report(&loaded_song, sample_rate, quality);
+    if freeze_gain {
+        loaded_song.normalization_gain = Some(gain);
+        if let Err(error) = save_normalization_gain(filename_in, gain) {
+            eprintln!("Could not store the gain in {}: {}", filename_in, error);
+            std::process::exit(1);
+        }
+    }
+
+    if json {
+        let summary = RenderSummary {
+            file: filename_in.to_string(),
+            output: (!stdout_pcm).then(|| filename_out.clone()),
+            length_seconds: (waveform.len() / CHANNELS as usize) as f32 / sample_rate as f32,
+            sample_rate,
+            peak_dbtp: linear_to_db(true_peak(&waveform, CHANNELS)),
+            gain,
+            gain_frozen: freeze_gain,
+            hash: deterministic.then(|| format!("{:016x}", waveform_hash(&waveform))),
+            warnings: song_warnings(&loaded_song),
+            report: report.clone(),
+            profile: profile.then(|| {
+                render_profile
+                    .instruments
+                    .iter()
+                    .map(|cost| InstrumentTime {
+                        instrument: cost.instrument.to_string(),
+                        notes: cost.notes,
+                        samples: cost.samples,
+                        milliseconds: cost.time.as_secs_f64() * 1000.0,
+                    })
+                    .collect()
+            }),
+        };
+        writeln!(log, "{}", serde_json::to_string_pretty(&summary).unwrap()).unwrap();
+    } else {
+        for line in report.summary(sample_rate) {
+            eprintln!("Warning: {}: {}", loaded_song.songname, line);
+        }
+        if freeze_gain {
+            writeln!(log, "Froze normalization gain {} in {}", gain, filename_in).unwrap();
+        }
+        if profile {
+            writeln!(log, "{}", render_profile).unwrap();
+        }
+        writeln!(log, "Peak: {:.1} dBTP", linear_to_db(true_peak(&waveform, CHANNELS))).unwrap();
+        if deterministic {
+            writeln!(log, "Render hash: {:016x}", waveform_hash(&waveform)).unwrap();
+        }
+    }
+
+    if stdout_pcm {
+        let frames = pcm_frames(&waveform, channels.unwrap_or(CHANNELS as usize));
+        let mut stdout = io::stdout().lock();
+        // A consumer that stops reading early, like `head`, isn't an error
+        if let Err(error) = stdout.write_all(&to_s16le(&frames)).and_then(|_| stdout.flush()) {
+            if error.kind() != io::ErrorKind::BrokenPipe {
+                panic!("Could not write PCM to stdout: {}", error);
+            }
+        }
+        return;
+    }
+
+    save_render(&loaded_song, &waveform, bit_depth.unwrap_or_default(), &filename_out).unwrap_or_else(|error| {
+        eprintln!("Could not write {}: {}", filename_out, error);
+        std::process::exit(1);
+    });
+
+    match start_at {
+        Some(marker) => {
+            let mut player = Player::with_tempo(waveform, loaded_song.tempo_map(), SAMPLE_RATE);
+            player.set_markers(&loaded_song.markers);
+            player.seek_to_marker(marker);
+            player.play();
+        }
+        None => play_interleaved(waveform, CHANNELS, SAMPLE_RATE, song_duration_secs),
+    }
+}
+
+// The stereo render as frames of `channels` samples: mono is the average of both sides,
+// more channels than two are silent
+fn pcm_frames(waveform: &[f32], channels: usize) -> Vec<f32> {
+    waveform
+        .chunks_exact(CHANNELS as usize)
+        .flat_map(|frame| match channels {
+            1 => vec![frame.iter().sum::<f32>() / frame.len() as f32],
+            _ => frame.iter().copied().chain(std::iter::repeat(0.0)).take(channels).collect(),
+        })
+        .collect()
+}
+
+// render --json output. Field names are part of the CLI's interface, keep them stable.
+#[derive(Serialize)]
+struct RenderSummary {
+    file: String,
+    output: Option<String>,  // None when written to stdout
+    length_seconds: f32,
+    sample_rate: u32,
+    peak_dbtp: f32,
+    gain: f32,               // normalization gain that was applied
+    gain_frozen: bool,       // whether it was just stored in the song file
+    hash: Option<String>,    // only for deterministic renders
+    warnings: Vec<String>,
+    report: RenderReport,
+    profile: Option<Vec<InstrumentTime>>,
+}
+
+#[derive(Serialize)]
+struct InstrumentTime {
+    instrument: String,
+    notes: usize,
+    samples: usize,
+    milliseconds: f64,
+}
+
+// Tags describing a rendered song, for WAV exports
+fn song_tags(song: &Song, waveform: &[f32]) -> WavTags {
+    WavTags {
+        title: Some(song.songname.clone()),
+        artist: Some(song.artist.clone()),
+        bpm: Some(song.bpm),
+        comment: None,
+        software: Some(format!("Synthia {}", env!("CARGO_PKG_VERSION"))),
+        replay_gain: replay_gain(waveform, CHANNELS, SAMPLE_RATE),
+    }
+}
+
+// Execute a render job file: transform every song it lists and write it in each format
+fn run(args: &[String]) {
+    let [filename] = args else { usage() };
+    let job = load_job(filename).unwrap_or_else(|error| {
+        eprintln!("Could not load job {}: {}", filename, error);
+        std::process::exit(1);
+    });
+
+    std::fs::create_dir_all(&job.destination).unwrap();
+    for song_filename in &job.songs {
+        let song = job.transform(&load_song(song_filename));
+        print_warnings(&song);
+
+        let (_, waveform, _, _, report) = render_song_with_report(&song, SAMPLE_RATE, RenderQuality::Final);
+        for line in report.summary(SAMPLE_RATE) {
+            eprintln!("Warning: {}: {}", song.songname, line);
+        }
+
+        for &format in &job.formats {
+            let filename_out = job.output_path(song_filename, format);
+            save_render(&song, &waveform, BitDepth::default(), &filename_out).unwrap_or_else(|error| {
+                eprintln!("Could not write {}: {}", filename_out, error);
+                std::process::exit(1);
+            });
+            println!("Wrote {}", filename_out);
+        }
+    }
+}
+
+// Save a render in the format registered for the file's extension
+fn save_render(song: &Song, waveform: &[f32], bit_depth: BitDepth, filename: &str) -> io::Result<()> {
+    let meta = ExportMeta { sample_rate: SAMPLE_RATE, channels: CHANNELS, tags: song_tags(song, waveform), bit_depth };
+    export_to_file(waveform, &meta, filename)
+}
+
+// Exit before rendering if nothing can write the output file
+fn check_export_format(filename: &str) {
+    let extension = Path::new(filename).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    if exporter_for(extension).is_none() {
+        eprintln!("Can't write {}, known formats are {}", filename, exporter_extensions().join(", "));
+        std::process::exit(1);
+    }
+}
+
+// Keep rendering the songs that appear or change in a folder. A file is only picked up
+// once it stopped changing between two polls, so songs still being written are left alone,
+// and songs whose render is newer than the song file are skipped, also after a restart.
+// Songs are any file with a registered importer. Problems are reported and the folder is
+// still watched; a song that can't be loaded or saved is reported once until it changes.
+fn watch(args: &[String]) {
+    let mut directory = None;
+    let mut directory_out = None;
+    let mut format = String::from("wav");
+    let mut interval_secs = 2.0f32;
+    let mut quality = RenderQuality::Final;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => directory_out = Some(args.next().unwrap_or_else(|| usage()).clone()),
+            "--format" => format = args.next().filter(|value| exporter_for(value).is_some()).unwrap_or_else(|| usage()).to_lowercase(),
+            "--interval" => interval_secs = args.next().and_then(|value| parse_duration(value)).unwrap_or_else(|| usage()),
+            "--quality" => quality = args.next().and_then(|value| parse_quality(value)).unwrap_or_else(|| usage()),
+            _ if directory.is_none() => directory = Some(arg.clone()),
+            _ => usage(),
+        }
+    }
+    let directory = directory.unwrap_or_else(|| usage());
+    let directory_out = directory_out.unwrap_or_else(|| directory.clone());
+    if let Err(error) = std::fs::create_dir_all(&directory_out) {
+        eprintln!("Could not create {}: {}", directory_out, error);
+        std::process::exit(1);
+    }
+
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    // Modification times seen on the previous poll, and of songs that failed to load or save
+    let mut seen: HashMap<PathBuf, SystemTime> = HashMap::new();
+    let mut failed: HashMap<PathBuf, SystemTime> = HashMap::new();
+    let interval = Duration::from_secs_f32(interval_secs);
+
+    println!("Watching {} for songs, rendering to {}", directory, directory_out);
+    loop {
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(error) => {
+                eprintln!("Could not read {}: {}", directory, error);
+                std::thread::sleep(interval);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|extension| extension.to_str()).and_then(importer_for).is_none() {
+                continue;
+            }
+            let Some(song_modified) = modified(&path) else { continue };
+            let settled = seen.insert(path.clone(), song_modified) == Some(song_modified);
+
+            let filename_out = Path::new(&directory_out).join(path.file_stem().unwrap()).with_extension(&format);
+            let up_to_date = modified(&filename_out).is_some_and(|render_modified| render_modified >= song_modified);
+            if !settled || up_to_date || failed.get(&path) == Some(&song_modified) {
+                continue;
+            }
+
+            // Playlists, sessions and job files share the songs' extension and are skipped
+            let filename = path.to_string_lossy();
+            let song = match Song::load(&filename) {
+                Ok(song) => song,
+                Err(error) => {
+                    eprintln!("Skipping {}: {}", filename, error);
+                    failed.insert(path.clone(), song_modified);
+                    continue;
+                }
+            };
+            print_warnings(&song);
+            let (_, waveform, _, _, _) = render_song_with_report(&song, SAMPLE_RATE, quality);
+            let filename_out = filename_out.to_string_lossy();
+            match save_render(&song, &waveform, BitDepth::default(), &filename_out) {
+                Ok(()) => println!("Rendered {} to {}", filename, filename_out),
+                Err(error) => {
+                    eprintln!("Could not write {}: {}", filename_out, error);
+                    failed.insert(path.clone(), song_modified);
+                }
+            }
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+// check --json output. Field names are part of the CLI's interface, keep them stable.
+#[derive(Serialize)]
+struct CheckResult {
+    file: String,
+    ok: bool,
+    failures: Vec<String>,
+    render: Option<CheckedRender>,  // None if the song couldn't be loaded
+}
+
+#[derive(Serialize)]
+struct CheckedRender {
+    length_seconds: f32,
+    sample_rate: u32,
+    peak_dbtp: f32,
+    non_finite_samples: usize,
+    clipped_percent: f32,
+    report: RenderReport,
+}
+
+// Render a song without playing or saving it, for CI of song repositories. Exits with
+// an error if the song has warnings, renders non-finite samples or clips more than
+// --max-clipping percent of its samples. With --json the result is printed as JSON.
+fn check(args: &[String]) {
+    let mut filename = None;
+    let mut json = false;
+    let mut quality = RenderQuality::Final;
+    let mut sample_rate = SAMPLE_RATE;
+    let mut max_clipping_percent = 0.1f32;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quality" => quality = args.next().and_then(|value| parse_quality(value)).unwrap_or_else(|| usage()),
+            "--rate" => sample_rate = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--json" => json = true,
+            "--max-clipping" => max_clipping_percent = args.next().and_then(|value| value.trim_end_matches('%').parse().ok()).unwrap_or_else(|| usage()),
+            _ if filename.is_none() => filename = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    let filename = filename.unwrap_or_else(|| usage());
+    let result = check_song(filename, quality, sample_rate, max_clipping_percent);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result).unwrap());
+    } else {
+        if let Some(render) = &result.render {
+            // Skipped notes and cut tails are worth knowing about, but are normal in many songs
+            for line in render.report.summary(sample_rate) {
+                println!("Note: {}", line);
+            }
+            println!("Rendered {:.1} s, peak {:.1} dBTP", render.length_seconds, render.peak_dbtp);
+        }
+        for failure in &result.failures {
+            eprintln!("FAILED: {}", failure);
+        }
+        if result.ok {
+            println!("OK: {}", filename);
+        }
+    }
+    if !result.ok {
+        std::process::exit(1);
+    }
+}
+
+fn check_song(filename: &str, quality: RenderQuality, sample_rate: u32, max_clipping_percent: f32) -> CheckResult {
+    let failed = |failure: String| CheckResult { file: filename.to_string(), ok: false, failures: vec![failure], render: None };
+    let song = match Song::load(filename) {
+        Ok(song) => song,
+        Err(error) => return failed(song_load_error(filename, &error)),
+    };
+
+    let mut failures = song_warnings(&song);
+    let (_, waveform, _, _, report) = render_song_with_report(&song, sample_rate, quality);
+
+    let non_finite_samples = waveform.iter().filter(|sample| !sample.is_finite()).count();
+    if non_finite_samples > 0 {
+        failures.push(format!("{} samples are NaN or infinite", non_finite_samples));
+    }
+    let clipped: usize = report.clipped_regions.iter().map(|region| region.end - region.start).sum();
+    let clipped_percent = clipped as f32 / (waveform.len() / CHANNELS as usize).max(1) as f32 * 100.0;
+    if clipped_percent > max_clipping_percent {
+        failures.push(format!("{:.2}% of the samples clip, more than the allowed {}%", clipped_percent, max_clipping_percent));
+    }
+
+    let render = CheckedRender {
+        length_seconds: (waveform.len() / CHANNELS as usize) as f32 / sample_rate as f32,
+        sample_rate,
+        peak_dbtp: linear_to_db(true_peak(&waveform, CHANNELS)),
+        non_finite_samples,
+        clipped_percent,
+        report,
+    };
+    CheckResult { file: filename.to_string(), ok: failures.is_empty(), failures, render: Some(render) }
+}
 
-    let loaded_song = load_from_json(filename_in);
+// Loop a song as an internet radio stream, e.g. for generative music
+fn stream(args: &[String]) {
+    let mut filename = None;
+    let mut port = 8000u16;
 
-    let sample_rate = 44100;
-    let (song_duration_secs, waveform) = generate_wave_from_packets(&loaded_song.packets, loaded_song.bpm, sample_rate);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            _ if filename.is_none() => filename = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    let song = load_song(filename.unwrap_or_else(|| usage()));
+    print_warnings(&song);
+
+    let (_, waveform, _) = render_song(&song, SAMPLE_RATE);
+    println!("Streaming {} - {} on http://0.0.0.0:{}/", song.artist, song.songname, port);
+    if let Err(error) = serve_stream(waveform, CHANNELS, SAMPLE_RATE, &format!("0.0.0.0:{}", port)) {
+        eprintln!("Could not stream on port {}: {}", port, error);
+        std::process::exit(1);
+    }
+}
+
+// Play a song or a playlist of songs back-to-back
+fn play(args: &[String]) {
+    let mut filename = None;
+    let mut crossfade_secs = 0.0f32;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--crossfade" => {
+                crossfade_secs = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage());
+            }
+            _ if filename.is_none() => filename = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    let filename = filename.unwrap_or_else(|| usage());
+
+    // Previously exported audio is played as-is
+    let extension = std::path::Path::new(filename).extension().and_then(|extension| extension.to_str());
+    if matches!(extension, Some("wav" | "flac" | "ogg" | "mp3")) {
+        if extension == Some("wav") {
+            if let Ok(WavTags { title: Some(title), artist, .. }) = read_wav_tags(filename) {
+                println!("Playing {} - {}", artist.as_deref().unwrap_or("Unknown"), title);
+            }
+        }
+        if let Err(error) = play_file(filename) {
+            eprintln!("Could not play {}: {}", filename, error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let songs = if is_playlist(filename) {
+        load_playlist(filename).and_then(|playlist| playlist.load_songs()).unwrap_or_else(|error| {
+            eprintln!("Could not load playlist {}: {}", filename, error);
+            std::process::exit(1);
+        })
+    } else {
+        vec![load_song(filename)]
+    };
+
+    // Pre-render everything so the songs follow each other without gaps
+    let waveforms: Vec<Vec<f32>> = songs
+        .iter()
+        .map(|song| {
+            println!("Rendering {} - {}", song.artist, song.songname);
+            print_warnings(song);
+            generate_wave_from_song(song, SAMPLE_RATE).1
+        })
+        .collect();
+
+    let crossfade_frames = (crossfade_secs * SAMPLE_RATE as f32) as usize;
+    let waveform = chain_waveforms(&waveforms, CHANNELS, crossfade_frames);
+    let duration_secs = (waveform.len() / CHANNELS as usize) as f32 / SAMPLE_RATE as f32;
+
+    play_interleaved(waveform, CHANNELS, SAMPLE_RATE, duration_secs);
+}
+
+// Scaffold a new song file from a template
+fn new(args: &[String]) {
+    let mut name = None;
+    let mut template = "blank";
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--template" => template = args.next().map(String::as_str).unwrap_or_else(|| usage()),
+            _ if name.is_none() => name = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    let name = name.unwrap_or_else(|| usage());
+    let filename = format!("{}.json", name);
+
+    if std::path::Path::new(&filename).exists() {
+        eprintln!("{} already exists", filename);
+        std::process::exit(1);
+    }
+
+    let song = song_from_template(template, name).unwrap_or_else(|| {
+        eprintln!("Unknown template \"{}\", available templates: {}", template, TEMPLATES.join(", "));
+        std::process::exit(1);
+    });
+
+    save_song(&song, &filename);
+    println!("Created {} from the {} template", filename, template);
+}
+
+fn parse_quality(text: &str) -> Option<RenderQuality> {
+    match text {
+        "preview" => Some(RenderQuality::Preview),
+        "final" => Some(RenderQuality::Final),
+        _ => None,
+    }
+}
+
+// Durations like "10s", "250ms" or plain seconds
+fn parse_duration(text: &str) -> Option<f32> {
+    if let Some(milliseconds) = text.strip_suffix("ms") {
+        milliseconds.parse::<f32>().ok().map(|milliseconds| milliseconds / 1000.0)
+    } else {
+        text.strip_suffix('s').unwrap_or(text).parse().ok()
+    }
+}
+
+// Generate a calibration signal and write it to a WAV or CSV file
+fn test_signal(args: &[String]) {
+    let mut positional = Vec::new();
+    let mut filename_out = None;
+    let mut level_db = -6.0f32;
+    let mut sample_rate = SAMPLE_RATE;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => filename_out = Some(args.next().unwrap_or_else(|| usage()).clone()),
+            "--level" => level_db = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--rate" => sample_rate = args.next().and_then(|value| value.parse().ok()).filter(|&rate| rate > 0).unwrap_or_else(|| usage()),
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let parse_hz = |text: &str| text.trim_end_matches("Hz").parse::<f32>().ok().filter(|hz| hz.is_finite() && *hz > 0.0).unwrap_or_else(|| usage());
+    let (signal, duration) = match positional.as_slice() {
+        ["sine", frequency, duration] => (TestSignal::Sine { frequency: parse_hz(frequency) }, duration),
+        ["sweep", from, to, duration] => (TestSignal::Sweep { from: parse_hz(from), to: parse_hz(to) }, duration),
+        ["white", duration] => (TestSignal::WhiteNoise, duration),
+        ["pink", duration] => (TestSignal::PinkNoise, duration),
+        ["impulse", duration] => (TestSignal::Impulse, duration),
+        _ => usage(),
+    };
+    let duration_secs = parse_duration(duration).unwrap_or_else(|| usage());
+    let filename_out = filename_out.unwrap_or_else(|| format!("{}.wav", positional[0]));
+    check_export_format(&filename_out);
+
+    let waveform = generate_test_signal(&signal, duration_secs, level_db, sample_rate);
+    export_to_file(&waveform, &ExportMeta::new(sample_rate), &filename_out).unwrap();
+    println!("Wrote {}", filename_out);
+}
+
+// Null test two renders: align them, subtract, and report what's left
+// With --threshold, exits with an error if the residual is louder than the given dB relative to the reference
+fn compare(args: &[String]) {
+    let mut filenames = Vec::new();
+    let mut max_offset = 1000usize;
+    let mut threshold_db = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-offset" => max_offset = args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()),
+            "--threshold" => threshold_db = Some(args.next().and_then(|value| value.parse::<f32>().ok()).unwrap_or_else(|| usage())),
+            _ => filenames.push(arg.as_str()),
+        }
+    }
+    let [reference_file, compared_file] = filenames[..] else { usage() };
+
+    let (reference, reference_rate) = read_render(reference_file);
+    let (compared, compared_rate) = read_render(compared_file);
+    if reference_rate != compared_rate {
+        eprintln!("Sample rates differ: {} Hz vs {} Hz", reference_rate, compared_rate);
+        std::process::exit(1);
+    }
+
+    let comparison = compare_waveforms(&reference, &compared, max_offset);
+    println!("Offset:        {} samples", comparison.offset);
+    println!("Compared:      {} samples", comparison.compared_samples);
+    if reference.len() != compared.len() {
+        println!("Length:        {} vs {} samples", reference.len(), compared.len());
+    }
+    println!("Residual RMS:  {:.1} dBFS ({:.1} dB relative)", comparison.residual_rms_db, comparison.relative_residual_db());
+    println!("Residual peak: {:.1} dBFS", comparison.residual_peak_db);
+
+    if let Some(threshold_db) = threshold_db {
+        if comparison.relative_residual_db() > threshold_db {
+            eprintln!("Residual is above the {} dB threshold", threshold_db);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn read_render(filename: &str) -> (Vec<f32>, u32) {
+    load_wav(filename).unwrap_or_else(|error| {
+        eprintln!("Could not read {}: {}", filename, error);
+        std::process::exit(1);
+    })
+}
+
+// Convert a song between Synthia's JSON and a Standard MIDI file, e.g. to open it in a DAW.
+// MIDI files get the song as it plays, with its repeats, dynamics and grooves applied.
+fn convert(args: &[String]) {
+    let [filename_in, filename_out] = args else { usage() };
+    let mut song = load_song(filename_in);
+    print_warnings(&song);
+    song.canonicalize();
+
+    match Path::new(filename_out).extension().and_then(|extension| extension.to_str()) {
+        Some("json") => save_song(&song, filename_out),
+        Some("mid" | "midi") => save_to_midi(&song, filename_out).unwrap_or_else(|error| {
+            eprintln!("Could not write {}: {}", filename_out, error);
+            std::process::exit(1);
+        }),
+        _ => usage(),
+    }
+    println!("Wrote {}", filename_out);
+}
+
+// Render every instrument and pitch a song uses as a separate WAV, for use in samplers
+fn one_shots(args: &[String]) {
+    let mut filename = None;
+    let mut directory = String::from("oneshots");
+    let mut length_secs = 2.0f32;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => directory = args.next().unwrap_or_else(|| usage()).clone(),
+            "--length" => length_secs = args.next().and_then(|value| parse_duration(value)).unwrap_or_else(|| usage()),
+            _ if filename.is_none() => filename = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    let song = load_song(filename.unwrap_or_else(|| usage()));
+
+    std::fs::create_dir_all(&directory).unwrap();
+    for (instrument, pitch) in used_notes(&song) {
+        let samples = render_one_shot(&instrument, pitch, length_secs, SAMPLE_RATE);
+        let filename_out = format!("{}/{}_{}.wav", directory, instrument, note_name(pitch));
+        save_wav(&samples, SAMPLE_RATE, &filename_out).unwrap();
+        println!("Wrote {}", filename_out);
+    }
+}
+
+// info --json output. Field names are part of the CLI's interface, keep them stable.
+#[derive(Serialize)]
+struct SongInfo {
+    title: String,
+    artist: String,
+    bpm: f32,
+    tempo_events: Vec<TempoChange>,
+    length_beats: f32,
+    length_seconds: f32,
+    notes: usize,
+    instruments: Vec<String>,
+    effects: Vec<EffectSettings>,
+    markers: Vec<Marker>,
+    warnings: Vec<String>,
+    analysis: Option<AnalysisInfo>,  // only with --analyze
+}
+
+#[derive(Serialize)]
+struct AnalysisInfo {
+    key: Option<String>,
+    chords: Vec<Option<String>>,  // one per bar, None where there is no chord
+}
+
+// Print an overview of a song; --analyze adds the key and the chord of every bar
+// With --json the overview is printed as JSON (see SongInfo)
+fn info(args: &[String]) {
+    let mut filename = None;
+    let mut analyze = false;
+    let mut json = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--analyze" => analyze = true,
+            "--json" => json = true,
+            _ if filename.is_none() => filename = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    let song = load_song(filename.unwrap_or_else(|| usage()));
+
+    let packets = song.mixed_packets();
+    let duration: Beats = packets.iter().map(|packet| packet.note_delta).sum();
+    let notes = notes_from_packets(&packets);
+    let mut instruments: Vec<String> = notes.iter().map(|note| note.instrument.to_string()).collect();
+    instruments.sort();
+    instruments.dedup();
+
+    if json {
+        let analysis = analyze.then(|| analyze_song(&song, 4)).map(|analysis| AnalysisInfo {
+            key: analysis.key.map(|key| key.to_string()),
+            chords: analysis.chords.iter().map(|chord| chord.as_ref().map(|chord| chord.to_string())).collect(),
+        });
+        let info = SongInfo {
+            title: song.songname.clone(),
+            artist: song.artist.clone(),
+            bpm: song.bpm,
+            tempo_events: song.tempo_events.clone(),
+            length_beats: duration.to_f32(),
+            length_seconds: song.tempo_map().seconds_at(duration) as f32,
+            notes: notes.len(),
+            instruments,
+            effects: song.effects.clone(),
+            markers: song.markers.clone(),
+            warnings: song_warnings(&song),
+            analysis,
+        };
+        println!("{}", serde_json::to_string_pretty(&info).unwrap());
+        return;
+    }
+
+    print_warnings(&song);
+
+    println!("{} - {}", song.artist, song.songname);
+    println!("Tempo:       {} bpm", song.bpm);
+    for change in &song.tempo_events {
+        println!("Tempo:       {} bpm from beat {}", change.bpm, change.beat);
+    }
+    println!("Length:      {} beats ({:.1} s)", duration, song.tempo_map().seconds_at(duration));
+    println!("Notes:       {}", notes.len());
+    println!("Instruments: {}", instruments.join(", "));
+    if !song.effects.is_empty() {
+        println!("Effects:     {}", song.effects.iter().map(EffectSettings::name).collect::<Vec<_>>().join(", "));
+    }
+    for marker in &song.markers {
+        println!("Marker:      {} at beat {}", marker.name, marker.beat);
+    }
+
+    if analyze {
+        let analysis = analyze_song(&song, 4);
+        match analysis.key {
+            Some(key) => println!("Key:         {}", key),
+            None => println!("Key:         unknown"),
+        }
+        for (bar, chord) in analysis.chords.iter().enumerate() {
+            match chord {
+                Some(chord) => println!("Bar {:>4}:    {}", bar + 1, chord),
+                None => println!("Bar {:>4}:    -", bar + 1),
+            }
+        }
+    }
+}
+
+// List a song's revisions, or with --add record the song's current state as a new one
+fn history(args: &[String]) {
+    let mut filename = None;
+    let mut summary = None;
+    let mut snapshot = true;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--add" => summary = Some(args.next().unwrap_or_else(|| usage()).as_str()),
+            "--no-snapshot" => snapshot = false,
+            _ if filename.is_none() => filename = Some(arg.as_str()),
+            _ => usage(),
+        }
+    }
+    let filename = filename.unwrap_or_else(|| usage());
+    let mut history = load_song_history(filename);
+
+    if let Some(summary) = summary {
+        let song = load_song(filename);
+        history.record(summary, snapshot.then_some(&song));
+        save_song_history(&history, filename);
+        println!("Recorded revision {} of {}", history.revisions.len(), filename);
+        return;
+    }
+
+    for (index, revision) in history.revisions.iter().enumerate() {
+        let snapshot = if revision.snapshot.is_some() { "" } else { " (no snapshot)" };
+        println!("{:>4}  {}  {}{}", index + 1, revision.date(), revision.summary, snapshot);
+    }
+}
+
+// Restore a song to one of its snapshots; its current state is recorded first,
+// so a revert can itself be reverted
+fn revert(args: &[String]) {
+    let (filename, number) = match args {
+        [filename, number] => (filename.as_str(), number.parse::<usize>().unwrap_or_else(|_| usage())),
+        _ => usage(),
+    };
+    let mut history = load_song_history(filename);
+
+    let snapshot = match number.checked_sub(1).and_then(|index| history.revisions.get(index)) {
+        Some(Revision { snapshot: Some(snapshot), .. }) => snapshot.clone(),
+        Some(_) => {
+            eprintln!("Revision {} of {} has no snapshot", number, filename);
+            std::process::exit(1);
+        }
+        None => {
+            eprintln!("{} has no revision {}", filename, number);
+            std::process::exit(1);
+        }
+    };
+
+    let current = load_song(filename);
+    history.record(&format!("Before reverting to revision {}", number), Some(&current));
+    save_song(&snapshot, filename);
+    save_song_history(&history, filename);
+    println!("Reverted {} to revision {}", filename, number);
+}
+
+fn load_song_history(filename: &str) -> History {
+    load_history(filename).unwrap_or_else(|error| {
+        eprintln!("Could not load the history of {}: {}", filename, error);
+        std::process::exit(1);
+    })
+}
 
-    save_vec_to_csv(waveform.clone(), filename_out).unwrap();
-    play_waveform(waveform, sample_rate, song_duration_secs);
+fn save_song_history(history: &History, filename: &str) {
+    if let Err(error) = save_history(history, filename) {
+        eprintln!("Could not save the history of {}: {}", filename, error);
+        std::process::exit(1);
+    }
 }
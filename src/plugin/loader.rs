@@ -0,0 +1,143 @@
+// Instrument plugins are shared libraries (.so/.dll/.dylib) exporting this C ABI:
+//
+//     typedef struct {
+//         uint32_t abi_version;   // must equal SYNTHIA_PLUGIN_ABI_VERSION
+//         const char *name;       // instrument name used in song files
+//     } SynthiaPluginDescription;
+//
+//     const SynthiaPluginDescription *synthia_describe(void);
+//     void *synthia_instantiate(void);
+//     float synthia_render(void *instance, float frequency, float time);
+//     void synthia_destroy(void *instance);
+//
+// synthia_render returns one sample of a note at `frequency` Hz, `time` seconds
+// after the note started, and may be called from any thread.
+
+use crate::song::register_instrument;
+
+use libloading::{Library, Symbol};
+use std::ffi::{c_char, c_void, CStr};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const SYNTHIA_PLUGIN_ABI_VERSION: u32 = 1;
+
+#[repr(C)]
+pub struct PluginDescription {
+    pub abi_version: u32,
+    pub name: *const c_char,
+}
+
+type DescribeFn = unsafe extern "C" fn() -> *const PluginDescription;
+type InstantiateFn = unsafe extern "C" fn() -> *mut c_void;
+type RenderFn = unsafe extern "C" fn(*mut c_void, f32, f32) -> f32;
+type DestroyFn = unsafe extern "C" fn(*mut c_void);
+
+#[derive(Debug)]
+pub enum PluginError {
+    Load(libloading::Error),
+    AbiMismatch { found: u32 },
+    InvalidDescription,
+    InstantiationFailed,
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PluginError::Load(error) => write!(f, "could not load plugin: {}", error),
+            PluginError::AbiMismatch { found } => {
+                write!(f, "plugin ABI version {} is not supported (expected {})", found, SYNTHIA_PLUGIN_ABI_VERSION)
+            }
+            PluginError::InvalidDescription => write!(f, "plugin returned an invalid description"),
+            PluginError::InstantiationFailed => write!(f, "plugin failed to create an instance"),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+impl From<libloading::Error> for PluginError {
+    fn from(error: libloading::Error) -> Self {
+        PluginError::Load(error)
+    }
+}
+
+// A plugin instance; the library stays loaded for as long as the instance lives
+struct LoadedPlugin {
+    instance: *mut c_void,
+    render: RenderFn,
+    destroy: DestroyFn,
+    _library: Library,
+}
+
+// The ABI requires synthia_render to be callable from any thread
+unsafe impl Send for LoadedPlugin {}
+unsafe impl Sync for LoadedPlugin {}
+
+impl LoadedPlugin {
+    fn render(&self, frequency: f32, time: f32) -> f32 {
+        unsafe { (self.render)(self.instance, frequency, time) }
+    }
+}
+
+impl Drop for LoadedPlugin {
+    fn drop(&mut self) {
+        unsafe { (self.destroy)(self.instance) }
+    }
+}
+
+// Load one plugin and register its instrument, returning the instrument name
+pub fn load_plugin(path: &Path) -> Result<String, PluginError> {
+    unsafe {
+        let library = Library::new(path)?;
+
+        let describe: Symbol<DescribeFn> = library.get(b"synthia_describe\0")?;
+        let instantiate: Symbol<InstantiateFn> = library.get(b"synthia_instantiate\0")?;
+        let render: RenderFn = *library.get::<RenderFn>(b"synthia_render\0")?;
+        let destroy: DestroyFn = *library.get::<DestroyFn>(b"synthia_destroy\0")?;
+
+        let description = describe().as_ref().ok_or(PluginError::InvalidDescription)?;
+        if description.abi_version != SYNTHIA_PLUGIN_ABI_VERSION {
+            return Err(PluginError::AbiMismatch { found: description.abi_version });
+        }
+        if description.name.is_null() {
+            return Err(PluginError::InvalidDescription);
+        }
+        let name = CStr::from_ptr(description.name)
+            .to_str()
+            .map_err(|_| PluginError::InvalidDescription)?
+            .to_string();
+
+        let instance = instantiate();
+        if instance.is_null() {
+            return Err(PluginError::InstantiationFailed);
+        }
+
+        let plugin = Arc::new(LoadedPlugin { instance, render, destroy, _library: library });
+        register_instrument(&name, Arc::new(move |frequency, time| plugin.render(frequency, time)));
+
+        Ok(name)
+    }
+}
+
+// Load every shared library in a directory, reporting the result per file
+pub fn load_plugins(directory: &Path) -> Vec<(String, Result<String, PluginError>)> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            matches!(path.extension().and_then(|extension| extension.to_str()), Some("so" | "dll" | "dylib"))
+        })
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| (path.display().to_string(), load_plugin(&path)))
+        .collect()
+}
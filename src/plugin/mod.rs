@@ -0,0 +1,3 @@
+mod loader;
+
+pub use loader::{PluginDescription, PluginError, load_plugin, load_plugins, SYNTHIA_PLUGIN_ABI_VERSION};
@@ -0,0 +1,205 @@
+use std::fmt;
+use super::beats::Beats;
+use super::note::{Note, notes_from_packets};
+use super::song::Song;
+use crate::units::pitch_class_name;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Suspended4,
+    Dominant7,
+    Major7,
+    Minor7,
+}
+
+impl ChordQuality {
+    const ALL: [ChordQuality; 8] = [
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+        ChordQuality::Suspended4,
+        ChordQuality::Dominant7,
+        ChordQuality::Major7,
+        ChordQuality::Minor7,
+    ];
+
+    // Semitones above the root
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Diminished => &[0, 3, 6],
+            ChordQuality::Augmented => &[0, 4, 8],
+            ChordQuality::Suspended4 => &[0, 5, 7],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10],
+            ChordQuality::Major7 => &[0, 4, 7, 11],
+            ChordQuality::Minor7 => &[0, 3, 7, 10],
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            ChordQuality::Major => "",
+            ChordQuality::Minor => "m",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+            ChordQuality::Suspended4 => "sus4",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Major7 => "maj7",
+            ChordQuality::Minor7 => "m7",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Chord {
+    pub root: u8,  // pitch class, 0 is C
+    pub quality: ChordQuality,
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", pitch_class_name(self.root), self.quality.suffix())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KeyMode {
+    Major,
+    Minor,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Key {
+    pub tonic: u8,  // pitch class, 0 is C
+    pub mode: KeyMode,
+}
+
+impl Key {
+    // Pitch classes of the scale, starting from the tonic (natural minor for minor keys)
+    pub fn scale(&self) -> [u8; 7] {
+        let steps = match self.mode {
+            KeyMode::Major => [0, 2, 4, 5, 7, 9, 11],
+            KeyMode::Minor => [0, 2, 3, 5, 7, 8, 10],
+        };
+        steps.map(|step| (self.tonic + step) % 12)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mode = match self.mode {
+            KeyMode::Major => "major",
+            KeyMode::Minor => "minor",
+        };
+        write!(f, "{} {}", pitch_class_name(self.tonic), mode)
+    }
+}
+
+// Chords per bar and the likely key of a song
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub key: Option<Key>,
+    pub chords: Vec<Option<Chord>>,  // one per bar, None where nothing sounds
+}
+
+// Krumhansl-Kessler key profiles: how well each scale degree fits a key
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+// Label the chord of every bar and estimate the key, from how long each pitch class
+// sounds. Songs have no time signature, so the bar length is passed in.
+pub fn analyze_song(song: &Song, beats_per_bar: u32) -> Analysis {
+    let notes = notes_from_packets(&song.mixed_packets());
+    let bar_length = Beats::whole(beats_per_bar.max(1) as i64);
+    let end = notes.iter().map(|note| note.start + note.duration).max().unwrap_or(Beats::ZERO);
+
+    let mut chords = Vec::new();
+    let mut bar_start = Beats::ZERO;
+    while bar_start < end {
+        chords.push(detect_chord(&notes, bar_start, bar_start + bar_length));
+        bar_start += bar_length;
+    }
+
+    Analysis { key: detect_key(&notes), chords }
+}
+
+// Best matching key for the notes, or None if there are none
+pub fn detect_key(notes: &[Note]) -> Option<Key> {
+    let end = notes.iter().map(|note| note.start + note.duration).max()?;
+    let weights = pitch_class_weights(notes, Beats::ZERO, end);
+
+    let mut best: Option<(f32, Key)> = None;
+    for tonic in 0..12u8 {
+        for (mode, profile) in [(KeyMode::Major, &MAJOR_PROFILE), (KeyMode::Minor, &MINOR_PROFILE)] {
+            let rotated: [f32; 12] = std::array::from_fn(|pitch_class| profile[(pitch_class + 12 - tonic as usize) % 12]);
+            let score = correlation(&weights, &rotated);
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, Key { tonic, mode }));
+            }
+        }
+    }
+
+    best.filter(|(score, _)| score.is_finite()).map(|(_, key)| key)
+}
+
+// Chord that best explains what sounds between `start` and `end`. Ties go to the
+// chord whose root is the lowest note, so inversions keep their name.
+pub fn detect_chord(notes: &[Note], start: Beats, end: Beats) -> Option<Chord> {
+    let weights = pitch_class_weights(notes, start, end);
+    if weights.iter().all(|weight| *weight == 0.0) {
+        return None;
+    }
+
+    let bass = notes
+        .iter()
+        .filter(|note| note.start < end && note.start + note.duration > start)
+        .map(|note| note.pitch)
+        .min()
+        .map(|pitch| pitch % 12);
+    let norm = weights.iter().map(|weight| weight * weight).sum::<f32>().sqrt();
+
+    let mut best: Option<(f32, Chord)> = None;
+    for root in 0..12u8 {
+        for quality in ChordQuality::ALL {
+            let intervals = quality.intervals();
+            // Cosine similarity between the weights and the chord's pitch classes
+            let matched: f32 = intervals.iter().map(|interval| weights[((root + interval) % 12) as usize]).sum();
+            let mut score = matched / (norm * (intervals.len() as f32).sqrt());
+            if Some(root) == bass {
+                score += 1e-3;
+            }
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, Chord { root, quality }));
+            }
+        }
+    }
+
+    best.map(|(_, chord)| chord)
+}
+
+// How many beats each pitch class sounds between `start` and `end`
+fn pitch_class_weights(notes: &[Note], start: Beats, end: Beats) -> [f32; 12] {
+    let mut weights = [0.0f32; 12];
+    for note in notes {
+        let overlap = (note.start + note.duration).min(end) - note.start.max(start);
+        if overlap > Beats::ZERO {
+            weights[(note.pitch % 12) as usize] += overlap.to_f32();
+        }
+    }
+    weights
+}
+
+fn correlation(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let mean_a = a.iter().sum::<f32>() / 12.0;
+    let mean_b = b.iter().sum::<f32>() / 12.0;
+    let covariance: f32 = a.iter().zip(b).map(|(a, b)| (a - mean_a) * (b - mean_b)).sum();
+    let spread_a = a.iter().map(|a| (a - mean_a).powi(2)).sum::<f32>().sqrt();
+    let spread_b = b.iter().map(|b| (b - mean_b).powi(2)).sum::<f32>().sqrt();
+    covariance / (spread_a * spread_b)
+}
@@ -0,0 +1,77 @@
+use serde::{Serialize, Deserialize};
+use super::midi_packet::MidiPacket;
+use super::note_status::NoteStatus;
+use super::song::Song;
+use super::dynamics::dynamics_scale;
+use super::beats::Beats;
+use super::groove::{load_groove, apply_groove};
+use super::mono::apply_mono;
+use crate::utils::random_unit;
+
+// When a note is allowed to play, based on how often the song has looped so far
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum TriggerCondition {
+    FirstPass,
+    NotFirstPass,
+    Every(u32),  // only on passes N, 2N, 3N, ... (1-based)
+}
+
+impl TriggerCondition {
+    fn holds(&self, pass: u32) -> bool {
+        match self {
+            TriggerCondition::FirstPass => pass == 1,
+            TriggerCondition::NotFirstPass => pass > 1,
+            TriggerCondition::Every(n) => pass.is_multiple_of(*n),
+        }
+    }
+}
+
+// Mix the song's tracks into its packets and unroll the repeats into one packet list, dropping notes whose
+// probability roll or trigger condition fails on a given pass.
+// Off packets are always kept; a dropped note's delta moves to the next kept packet.
+// Dynamics markings are applied to the velocities of the notes that play,
+// then the song's grooves (those that can be loaded) and mono modes are applied to the result.
+// With fold_octaves, notes outside their instrument's range are moved into it.
+pub fn flatten_packets(song: &Song) -> Vec<MidiPacket> {
+    let packets = song.mixed_packets();
+    let mut flattened = Vec::with_capacity(packets.len() * song.repeat as usize);
+    let mut carried_delta = Beats::ZERO;
+
+    for pass in 1..=song.repeat {
+        let mut beat = Beats::ZERO;
+
+        for (index, packet) in packets.iter().enumerate() {
+            beat += packet.note_delta;
+            let roll_index = (pass as u64 - 1) * packets.len() as u64 + index as u64;
+            let plays = packet.note_status == NoteStatus::Off
+                || (packet.condition.as_ref().is_none_or(|condition| condition.holds(pass))
+                    && random_unit(song.seed, roll_index) < packet.probability);
+
+            if plays {
+                let pitch = if song.fold_octaves { packet.instrument.fold_into_range(packet.pitch) } else { packet.pitch };
+                flattened.push(MidiPacket {
+                    pitch,
+                    note_delta: packet.note_delta + carried_delta,
+                    velocity: packet.velocity * dynamics_scale(&song.dynamics, beat.to_f32()),
+                    ..packet.clone()
+                });
+                carried_delta = Beats::ZERO;
+            } else {
+                carried_delta += packet.note_delta;
+            }
+        }
+    }
+
+    // A groove that can't be loaded is a song warning, the notes play without it
+    for assignment in &song.grooves {
+        if let Ok(groove) = load_groove(&assignment.groove) {
+            flattened = apply_groove(&flattened, &groove, assignment.instrument.as_ref(), song.pairing);
+        }
+    }
+
+    for mode in &song.mono {
+        flattened = apply_mono(&flattened, mode, song.pairing);
+    }
+
+    flattened
+}
@@ -0,0 +1,10 @@
+use serde::{Serialize, Deserialize};
+
+// How a note is played, as marked in notation. Set on On packets; the renderer
+// interprets them, so imported articulations aren't lost.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Articulation {
+    Staccato,  // sounds for half its length and is damped
+    Accent,    // louder, with a harder attack
+    Legato,    // held until the instrument's next note starts
+}
@@ -0,0 +1,382 @@
+use serde::{Serialize, Deserialize, Serializer, Deserializer, de};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, AddAssign, Sub, SubAssign, Neg};
+
+// An exact musical duration or position in beats (quarter notes), stored as a
+// reduced fraction so triplets and long songs don't accumulate float drift.
+//
+// In JSON a duration can be a number (0.5), a fraction ("1/3"), or a note value
+// ("quarter", "dotted-eighth", "triplet-eighth", ...).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Beats {
+    numerator: i64,
+    denominator: i64,  // always positive, and coprime with the numerator
+}
+
+const MAX_APPROXIMATION_DENOMINATOR: i64 = 100_000;
+
+impl Beats {
+    pub const ZERO: Beats = Beats { numerator: 0, denominator: 1 };
+
+    pub fn new(numerator: i64, denominator: i64) -> Self {
+        assert!(denominator != 0, "Beats denominator must not be zero");
+        Self::reduced(numerator as i128, denominator as i128)
+            .expect("Beats fraction out of range")
+    }
+
+    pub fn whole(beats: i64) -> Self {
+        Beats { numerator: beats, denominator: 1 }
+    }
+
+    pub fn numerator(&self) -> i64 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> i64 {
+        self.denominator
+    }
+
+    // Closest fraction to a float: exact for binary fractions like 0.375, and
+    // recovers simple ratios like 1/3 from their rounded decimal form
+    pub fn from_f64(value: f64) -> Self {
+        if !value.is_finite() {
+            return Beats::ZERO;
+        }
+
+        // Binary fractions (everything written by older versions) convert exactly
+        let scaled = value * (1u64 << 20) as f64;
+        if scaled.fract() == 0.0 && scaled.abs() < i64::MAX as f64 {
+            return Beats::new(scaled as i64, 1 << 20);
+        }
+
+        // Continued fraction expansion, stopping once close enough
+        let (mut previous_numerator, mut numerator) = (0i128, 1i128);
+        let (mut previous_denominator, mut denominator) = (1i128, 0i128);
+        let mut remainder = value.abs();
+        loop {
+            let whole = remainder.floor();
+            let next_numerator = (whole as i128).saturating_mul(numerator).saturating_add(previous_numerator);
+            let next_denominator = (whole as i128).saturating_mul(denominator).saturating_add(previous_denominator);
+            if next_denominator > MAX_APPROXIMATION_DENOMINATOR as i128 || next_numerator > i64::MAX as i128 {
+                break;
+            }
+            (previous_numerator, numerator) = (numerator, next_numerator);
+            (previous_denominator, denominator) = (denominator, next_denominator);
+
+            let approximation = numerator as f64 / denominator as f64;
+            if (approximation - value.abs()).abs() <= 1e-6 || remainder == whole {
+                break;
+            }
+            remainder = 1.0 / (remainder - whole);
+        }
+
+        if denominator == 0 {
+            return Beats::ZERO;
+        }
+        let sign = if value < 0.0 { -1 } else { 1 };
+        Self::reduced(sign * numerator, denominator).unwrap_or(Beats::ZERO)
+    }
+
+    pub fn from_f32(value: f32) -> Self {
+        Self::from_f64(value as f64)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    pub fn to_f32(&self) -> f32 {
+        self.to_f64() as f32
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.numerator < 0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator == 0
+    }
+
+    pub fn abs(&self) -> Self {
+        if self.is_negative() { -*self } else { *self }
+    }
+
+    // A note value name such as "quarter", "dotted-eighth" or "triplet-sixteenth"
+    pub fn from_note_value(name: &str) -> Option<Self> {
+        let (modifier, base) = match name.split_once('-') {
+            Some((modifier @ ("dotted" | "triplet"), base)) => (Some(modifier), base),
+            Some(("double", rest)) => match rest.split_once('-') {
+                Some(("dotted", base)) => (Some("double-dotted"), base),
+                _ => return None,
+            },
+            _ => (None, name),
+        };
+
+        let base = match base {
+            "whole" => Beats::whole(4),
+            "half" => Beats::whole(2),
+            "quarter" => Beats::whole(1),
+            "eighth" => Beats::new(1, 2),
+            "sixteenth" => Beats::new(1, 4),
+            "thirty-second" => Beats::new(1, 8),
+            "sixty-fourth" => Beats::new(1, 16),
+            _ => return None,
+        };
+
+        Some(match modifier {
+            Some("dotted") => base + Beats::new(base.numerator, base.denominator * 2),
+            Some("double-dotted") => base + Beats::new(base.numerator * 3, base.denominator * 4),
+            Some("triplet") => Beats::new(base.numerator * 2, base.denominator * 3),
+            _ => base,
+        })
+    }
+
+    // Parse "3", "-1/3", "0.25" or a note value name
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some((numerator, denominator)) = text.split_once('/') {
+            let numerator: i64 = numerator.trim().parse().ok()?;
+            let denominator: i64 = denominator.trim().parse().ok()?;
+            if denominator == 0 {
+                return None;
+            }
+            return Self::reduced(numerator as i128, denominator as i128);
+        }
+        if let Ok(value) = text.parse::<f64>() {
+            return value.is_finite().then(|| Beats::from_f64(value));
+        }
+        Beats::from_note_value(text)
+    }
+
+    fn reduced(numerator: i128, denominator: i128) -> Option<Self> {
+        let sign = if denominator < 0 { -1 } else { 1 };
+        let divisor = gcd(numerator.abs(), denominator.abs()).max(1);
+        Some(Beats {
+            numerator: i64::try_from(sign * numerator / divisor).ok()?,
+            denominator: i64::try_from(denominator.abs() / divisor).ok()?,
+        })
+    }
+
+    // Denominators that are powers of two are exactly representable as floats
+    fn is_binary_fraction(&self) -> bool {
+        self.denominator.count_ones() == 1
+    }
+}
+
+pub(super) fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Default for Beats {
+    fn default() -> Self {
+        Beats::ZERO
+    }
+}
+
+impl Add for Beats {
+    type Output = Beats;
+
+    fn add(self, other: Beats) -> Beats {
+        let numerator = self.numerator as i128 * other.denominator as i128 + other.numerator as i128 * self.denominator as i128;
+        let denominator = self.denominator as i128 * other.denominator as i128;
+        // Fall back to an approximation rather than overflowing on absurd denominators
+        Beats::reduced(numerator, denominator).unwrap_or_else(|| Beats::from_f64(self.to_f64() + other.to_f64()))
+    }
+}
+
+impl Sub for Beats {
+    type Output = Beats;
+
+    fn sub(self, other: Beats) -> Beats {
+        self + (-other)
+    }
+}
+
+impl Neg for Beats {
+    type Output = Beats;
+
+    // Only a whole i64::MIN (which a song file can hold) has no negation, it saturates
+    fn neg(self) -> Beats {
+        Beats { numerator: self.numerator.checked_neg().unwrap_or(i64::MAX), denominator: self.denominator }
+    }
+}
+
+impl AddAssign for Beats {
+    fn add_assign(&mut self, other: Beats) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Beats {
+    fn sub_assign(&mut self, other: Beats) {
+        *self = *self - other;
+    }
+}
+
+impl std::iter::Sum for Beats {
+    fn sum<I: Iterator<Item = Beats>>(iter: I) -> Beats {
+        iter.fold(Beats::ZERO, Add::add)
+    }
+}
+
+impl Ord for Beats {
+    fn cmp(&self, other: &Beats) -> Ordering {
+        (self.numerator as i128 * other.denominator as i128).cmp(&(other.numerator as i128 * self.denominator as i128))
+    }
+}
+
+impl PartialOrd for Beats {
+    fn partial_cmp(&self, other: &Beats) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Beats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+// Binary fractions are written as plain numbers, so existing song files round-trip unchanged
+impl Serialize for Beats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_binary_fraction() {
+            serializer.serialize_f64(self.to_f64())
+        } else {
+            serializer.serialize_str(&self.to_string())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Beats {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BeatsVisitor)
+    }
+}
+
+struct BeatsVisitor;
+
+impl de::Visitor<'_> for BeatsVisitor {
+    type Value = Beats;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number of beats, a fraction like \"1/3\" or a note value like \"dotted-eighth\"")
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Beats, E> {
+        if value.is_finite() {
+            Ok(Beats::from_f64(value))
+        } else {
+            Err(E::invalid_value(de::Unexpected::Float(value), &self))
+        }
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Beats, E> {
+        i64::try_from(value)
+            .map(Beats::whole)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Beats, E> {
+        Ok(Beats::whole(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Beats, E> {
+        Beats::parse(value).ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_f64_is_exact_for_binary_fractions() {
+        assert_eq!(Beats::from_f64(0.375), Beats::new(3, 8));
+        assert_eq!(Beats::from_f64(-2.5), Beats::new(-5, 2));
+        assert_eq!(Beats::from_f64(0.0), Beats::ZERO);
+        assert_eq!(Beats::from_f64(f64::NAN), Beats::ZERO);
+    }
+
+    #[test]
+    fn from_f64_recovers_simple_ratios() {
+        assert_eq!(Beats::from_f64(0.333333), Beats::new(1, 3));
+        assert_eq!(Beats::from_f64(1.0 / 3.0), Beats::new(1, 3));
+        assert_eq!(Beats::from_f64(-0.666667), Beats::new(-2, 3));
+        assert_eq!(Beats::from_f64(0.2), Beats::new(1, 5));
+    }
+
+    #[test]
+    fn note_values() {
+        assert_eq!(Beats::from_note_value("quarter"), Some(Beats::whole(1)));
+        assert_eq!(Beats::from_note_value("dotted-eighth"), Some(Beats::new(3, 4)));
+        assert_eq!(Beats::from_note_value("dotted-half"), Some(Beats::whole(3)));
+        assert_eq!(Beats::from_note_value("double-dotted-quarter"), Some(Beats::new(7, 4)));
+        assert_eq!(Beats::from_note_value("triplet-eighth"), Some(Beats::new(1, 3)));
+        assert_eq!(Beats::from_note_value("triplet-quarter"), Some(Beats::new(2, 3)));
+        assert_eq!(Beats::from_note_value("dotted-thirty-second"), Some(Beats::new(3, 16)));
+        assert_eq!(Beats::from_note_value("double-triplet-quarter"), None);
+        assert_eq!(Beats::from_note_value("dotted-crotchet"), None);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Beats::parse("3"), Some(Beats::whole(3)));
+        assert_eq!(Beats::parse(" -1/3 "), Some(Beats::new(-1, 3)));
+        assert_eq!(Beats::parse("2/4"), Some(Beats::new(1, 2)));
+        assert_eq!(Beats::parse("1/-3"), Some(Beats::new(-1, 3)));
+        assert_eq!(Beats::parse("0.25"), Some(Beats::new(1, 4)));
+        assert_eq!(Beats::parse("dotted-eighth"), Some(Beats::new(3, 4)));
+        assert_eq!(Beats::parse("1/0"), None);
+        assert_eq!(Beats::parse("inf"), None);
+        assert_eq!(Beats::parse("a/3"), None);
+    }
+
+    #[test]
+    fn serializes_binary_fractions_as_numbers() {
+        assert_eq!(serde_json::to_string(&Beats::new(3, 8)).unwrap(), "0.375");
+        assert_eq!(serde_json::to_string(&Beats::whole(2)).unwrap(), "2.0");
+        assert_eq!(serde_json::to_string(&Beats::new(1, 3)).unwrap(), "\"1/3\"");
+        assert_eq!(serde_json::to_string(&Beats::new(-7, 6)).unwrap(), "\"-7/6\"");
+
+        for beats in [Beats::ZERO, Beats::new(3, 8), Beats::new(1, 3), Beats::new(-7, 6), Beats::whole(-5)] {
+            let json = serde_json::to_string(&beats).unwrap();
+            assert_eq!(serde_json::from_str::<Beats>(&json).unwrap(), beats);
+        }
+        assert_eq!(serde_json::from_str::<Beats>("\"triplet-eighth\"").unwrap(), Beats::new(1, 3));
+        assert!(serde_json::from_str::<Beats>("\"1/0\"").is_err());
+    }
+
+    #[test]
+    fn orders_across_denominators() {
+        assert!(Beats::new(1, 3) < Beats::new(1, 2));
+        assert!(Beats::new(2, 3) > Beats::new(5, 8));
+        assert!(Beats::new(-1, 3) < Beats::new(-1, 4));
+        assert_eq!(Beats::new(2, 6).cmp(&Beats::new(1, 3)), Ordering::Equal);
+        assert_eq!(Beats::new(7, 3).max(Beats::new(9, 4)), Beats::new(7, 3));
+    }
+
+    #[test]
+    fn arithmetic_stays_exact() {
+        let third = Beats::new(1, 3);
+        assert_eq!(third + third + third, Beats::whole(1));
+        assert_eq!(Beats::new(1, 2) - third, Beats::new(1, 6));
+        assert_eq!([third; 6].into_iter().sum::<Beats>(), Beats::whole(2));
+    }
+
+    #[test]
+    fn negating_the_smallest_whole_saturates() {
+        let smallest: Beats = serde_json::from_str(&i64::MIN.to_string()).unwrap();
+        assert_eq!(-smallest, Beats::whole(i64::MAX));
+        assert_eq!(smallest.abs(), Beats::whole(i64::MAX));
+        assert_eq!(Beats::new(-1, 3).abs(), Beats::new(1, 3));
+    }
+}
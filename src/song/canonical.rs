@@ -0,0 +1,177 @@
+use super::beats::Beats;
+use super::midi_packet::MidiPacket;
+use super::note_status::NoteStatus;
+use super::song::Song;
+
+impl Song {
+    // Rewrite the packets into one canonical order so that songs describing the same
+    // music render identically, whatever produced the JSON:
+    // - packets are placed at their absolute position, so negative deltas become
+    //   reordering (nothing starts before the beginning of the song)
+    // - simultaneous packets are sorted Offs first, then Ons, by pitch and instrument;
+    //   the Off of a zero-length note stays after its On
+    // - duplicate simultaneous events for the same note are merged, keeping the
+    //   loudest velocity
+    // Every track's packets are rewritten the same way.
+    pub fn canonicalize(&mut self) {
+        self.packets = canonical_packets(&self.packets);
+        for track in self.tracks.iter_mut() {
+            track.packets = canonical_packets(&track.packets);
+        }
+    }
+}
+
+fn canonical_packets(packets: &[MidiPacket]) -> Vec<MidiPacket> {
+    let mut position = Beats::ZERO;
+    let mut events: Vec<(Beats, u8, usize, MidiPacket)> = packets
+        .iter()
+        .enumerate()
+        .map(|(index, packet)| {
+            position += packet.note_delta;
+            (position.max(Beats::ZERO), 0, index, packet.clone())
+        })
+        .collect();
+
+    for index in 0..events.len() {
+        events[index].1 = match events[index].3.note_status {
+            NoteStatus::On => 1,
+            NoteStatus::Off if ends_zero_length_note(&events, index) => 2,
+            NoteStatus::Off => 0,
+        };
+    }
+
+    events.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(a.1.cmp(&b.1))
+            .then(a.3.pitch.cmp(&b.3.pitch))
+            .then(a.3.instrument.name().cmp(b.3.instrument.name()))
+            .then(a.2.cmp(&b.2))
+    });
+
+    let mut packets: Vec<MidiPacket> = Vec::with_capacity(events.len());
+    let mut previous_position = Beats::ZERO;
+    let mut previous_event: Option<(Beats, u8)> = None;
+
+    for (position, rank, _, packet) in events {
+        if let Some(last) = packets.last_mut() {
+            let duplicate = previous_event == Some((position, rank))
+                && last.pitch == packet.pitch
+                && last.instrument == packet.instrument;
+            if duplicate {
+                last.velocity = last.velocity.max(packet.velocity);
+                continue;
+            }
+        }
+
+        packets.push(MidiPacket { note_delta: position - previous_position, ..packet });
+        previous_position = position;
+        previous_event = Some((position, rank));
+    }
+
+    packets
+}
+
+// Whether the Off at `index` closes a note that started at the same position,
+// pairing Ons with the next matching Off the way the renderer does
+fn ends_zero_length_note(events: &[(Beats, u8, usize, MidiPacket)], index: usize) -> bool {
+    let (position, _, _, off) = &events[index];
+    let previous = events[..index].iter().rev().find(|(_, _, _, packet)| {
+        packet.pitch == off.pitch && packet.instrument == off.instrument
+    });
+    matches!(previous, Some((start, _, _, packet)) if packet.note_status == NoteStatus::On && start == position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::{Instrument, Track};
+
+    fn on(pitch: u8, delta: Beats, velocity: f32) -> MidiPacket {
+        MidiPacket::new(pitch, Instrument::Sine, NoteStatus::On, delta, velocity)
+    }
+
+    fn off(pitch: u8, delta: Beats) -> MidiPacket {
+        MidiPacket::new(pitch, Instrument::Sine, NoteStatus::Off, delta, 0.0)
+    }
+
+    fn canonical(packets: Vec<MidiPacket>) -> Vec<MidiPacket> {
+        let mut song = Song::new("test", "", 120.0);
+        song.packets = packets;
+        song.canonicalize();
+        song.packets
+    }
+
+    fn one() -> Beats {
+        Beats::whole(1)
+    }
+
+    #[test]
+    fn negative_deltas_become_reordering() {
+        let packets = canonical(vec![
+            on(60, Beats::ZERO, 1.0),
+            on(62, Beats::whole(2), 1.0),
+            off(60, Beats::whole(-1)),
+            off(62, Beats::whole(2)),
+        ]);
+        assert_eq!(packets, vec![on(60, Beats::ZERO, 1.0), off(60, one()), on(62, one(), 1.0), off(62, one())]);
+    }
+
+    #[test]
+    fn nothing_starts_before_the_song() {
+        let packets = canonical(vec![on(60, Beats::whole(-2), 1.0), off(60, one())]);
+        assert_eq!(packets, vec![on(60, Beats::ZERO, 1.0), off(60, Beats::ZERO)]);
+    }
+
+    #[test]
+    fn offs_come_before_ons() {
+        let packets = canonical(vec![on(60, Beats::ZERO, 1.0), on(62, one(), 1.0), off(60, Beats::ZERO), off(62, one())]);
+        assert_eq!(packets, vec![on(60, Beats::ZERO, 1.0), off(60, one()), on(62, Beats::ZERO, 1.0), off(62, one())]);
+    }
+
+    #[test]
+    fn simultaneous_events_are_sorted_by_pitch() {
+        let packets = canonical(vec![on(64, Beats::ZERO, 1.0), on(60, Beats::ZERO, 1.0), off(64, one()), off(60, Beats::ZERO)]);
+        assert_eq!(packets, vec![on(60, Beats::ZERO, 1.0), on(64, Beats::ZERO, 1.0), off(60, one()), off(64, Beats::ZERO)]);
+    }
+
+    #[test]
+    fn zero_length_notes_keep_their_off_after_the_on() {
+        let packets = canonical(vec![on(64, Beats::ZERO, 1.0), on(60, one(), 1.0), off(60, Beats::ZERO), off(64, Beats::ZERO)]);
+        assert_eq!(packets, vec![on(64, Beats::ZERO, 1.0), off(64, one()), on(60, Beats::ZERO, 1.0), off(60, Beats::ZERO)]);
+    }
+
+    #[test]
+    fn duplicates_merge_keeping_the_loudest() {
+        let packets = canonical(vec![on(60, Beats::ZERO, 0.5), on(60, Beats::ZERO, 0.9), off(60, one()), off(60, Beats::ZERO)]);
+        assert_eq!(packets, vec![on(60, Beats::ZERO, 0.9), off(60, one())]);
+    }
+
+    #[test]
+    fn canonicalizing_twice_changes_nothing() {
+        let mut song = Song::new("test", "", 120.0);
+        song.packets = vec![
+            on(67, Beats::new(1, 3), 0.7),
+            on(60, Beats::ZERO, 0.5),
+            off(67, Beats::new(-1, 3)),
+            on(60, Beats::ZERO, 0.8),
+            on(64, Beats::new(1, 2), 1.0),
+            off(64, Beats::ZERO),
+            off(60, Beats::whole(2)),
+            off(60, Beats::ZERO),
+        ];
+        song.canonicalize();
+        let once = song.clone();
+        song.canonicalize();
+        assert_eq!(song, once);
+    }
+
+    #[test]
+    fn tracks_are_canonicalized() {
+        let mut track = Track::new("Bass", Instrument::Sine);
+        track.packets = vec![on(40, Beats::ZERO, 1.0), on(43, Beats::whole(2), 1.0), off(40, Beats::whole(-1)), off(43, Beats::whole(2))];
+        let mut song = Song::new("test", "", 120.0);
+        song.tracks.push(track);
+        song.canonicalize();
+        assert_eq!(song.tracks[0].packets, vec![on(40, Beats::ZERO, 1.0), off(40, one()), on(43, one(), 1.0), off(43, one())]);
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Serialize, Deserialize};
+use super::instrument::Instrument;
+
+// Analog-style imperfection for an instrument, against the sterile sound of perfect
+// digital oscillators: every voice wanders slowly in pitch, note lengths vary a little
+// and a faint noise floor plays under the notes. The defaults are a subtle setting,
+// so listing just the instrument turns it on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub instrument: Instrument,
+    #[serde(default = "default_pitch_cents")]
+    pub pitch_cents: f32,       // how far a voice wanders, up or down
+    #[serde(default = "default_rate")]
+    pub rate: f32,              // how fast it wanders, in Hz
+    #[serde(default = "default_length")]
+    pub length: f32,            // maximum relative change of note lengths, e.g. 0.02 for +-2%
+    #[serde(default = "default_noise_db")]
+    pub noise_db: Option<f32>,  // noise floor level under the notes in dBFS, None for no noise
+    #[serde(default)]
+    pub seed: u64,
+}
+
+fn default_pitch_cents() -> f32 {
+    3.0
+}
+
+fn default_rate() -> f32 {
+    0.5
+}
+
+fn default_length() -> f32 {
+    0.02
+}
+
+fn default_noise_db() -> Option<f32> {
+    Some(-78.0)
+}
@@ -0,0 +1,70 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DynamicLevel {
+    Ppp,
+    Pp,
+    P,
+    Mp,
+    Mf,
+    F,
+    Ff,
+    Fff,
+}
+
+impl DynamicLevel {
+    // Velocity multiplier for notes played at this level
+    pub fn velocity_scale(&self) -> f32 {
+        match self {
+            DynamicLevel::Ppp => 0.2,
+            DynamicLevel::Pp => 0.3,
+            DynamicLevel::P => 0.45,
+            DynamicLevel::Mp => 0.6,
+            DynamicLevel::Mf => 0.75,
+            DynamicLevel::F => 0.85,
+            DynamicLevel::Ff => 0.95,
+            DynamicLevel::Fff => 1.0,
+        }
+    }
+}
+
+// Dynamics annotations, positioned in beats from the start of the song
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Dynamic {
+    Mark { beat: f32, level: DynamicLevel },
+    // Crescendo or diminuendo from the level in effect at `start` to `to`
+    Hairpin { start: f32, end: f32, to: DynamicLevel },
+}
+
+impl Dynamic {
+    fn start(&self) -> f32 {
+        match self {
+            Dynamic::Mark { beat, .. } => *beat,
+            Dynamic::Hairpin { start, .. } => *start,
+        }
+    }
+}
+
+// Velocity multiplier at a beat position; notes before the first marking are unchanged
+pub fn dynamics_scale(dynamics: &[Dynamic], beat: f32) -> f32 {
+    let mut ordered: Vec<&Dynamic> = dynamics.iter().collect();
+    ordered.sort_by(|a, b| a.start().total_cmp(&b.start()));
+
+    let mut scale = 1.0;
+    for dynamic in ordered.into_iter().take_while(|dynamic| dynamic.start() <= beat) {
+        scale = match dynamic {
+            Dynamic::Mark { level, .. } => level.velocity_scale(),
+            Dynamic::Hairpin { start, end, to } => {
+                if beat >= *end {
+                    to.velocity_scale()
+                } else {
+                    let progress = (beat - start) / (end - start);
+                    scale + (to.velocity_scale() - scale) * progress
+                }
+            }
+        };
+    }
+
+    scale
+}
@@ -0,0 +1,36 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+// Longer delay times are shortened to this many seconds, the delay line holds that
+// much of the mix in memory
+pub const MAX_DELAY_TIME: f32 = 5.0;
+
+// An effect applied to the whole mix before it is normalized, e.g.
+// `{"Delay": {"time": 0.375, "feedback": 0.4, "mix": 0.3}}`. Effects run in the order
+// the song lists them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum EffectSettings {
+    Gain { db: f32 },
+    LowPass { cutoff: f32 },   // Hz
+    HighPass { cutoff: f32 },  // Hz
+    // Echoes `time` seconds apart, each `feedback` times the last, mixed in at `mix`
+    Delay { time: f32, feedback: f32, mix: f32 },
+    // An effect registered by the program rendering the song
+    Custom {
+        name: String,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        params: BTreeMap<String, f32>,
+    },
+}
+
+impl EffectSettings {
+    pub fn name(&self) -> &str {
+        match self {
+            EffectSettings::Gain { .. } => "Gain",
+            EffectSettings::LowPass { .. } => "LowPass",
+            EffectSettings::HighPass { .. } => "HighPass",
+            EffectSettings::Delay { .. } => "Delay",
+            EffectSettings::Custom { name, .. } => name,
+        }
+    }
+}
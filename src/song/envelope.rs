@@ -0,0 +1,28 @@
+use serde::{Serialize, Deserialize};
+use super::instrument::Instrument;
+
+// Attack/decay/sustain/release shape of a note's loudness. Times are in seconds,
+// sustain is the level held after the decay, from 0 to 1.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Envelope {
+    #[serde(default)]
+    pub attack: f32,
+    #[serde(default)]
+    pub decay: f32,
+    #[serde(default = "default_sustain")]
+    pub sustain: f32,
+    #[serde(default)]
+    pub release: f32,  // after the note's Off
+}
+
+fn default_sustain() -> f32 {
+    1.0
+}
+
+// The envelope of every note of an instrument that doesn't set its own
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstrumentEnvelope {
+    pub instrument: Instrument,
+    #[serde(flatten)]
+    pub envelope: Envelope,
+}
@@ -0,0 +1,57 @@
+use serde::{Serialize, Deserialize};
+use std::fs::File;
+use std::io::{self, Read};
+use super::beats::Beats;
+use super::instrument::Instrument;
+use super::midi_packet::MidiPacket;
+use super::note::{notes_from_packets_paired, packets_from_notes};
+use super::pairing::NotePairing;
+
+pub const GROOVE_DIRECTORY: &str = "grooves";
+
+// Timing and velocity offsets per sixteenth note, repeating every `timing.len()` steps
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Groove {
+    pub name: String,
+    pub timing: Vec<f32>,    // shift per step, in sixteenths (0.1 = a tenth of a sixteenth late)
+    pub velocity: Vec<f32>,  // velocity multiplier per step
+}
+
+// Which groove a song applies, and to which instrument (all notes if none)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GrooveAssignment {
+    pub groove: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instrument: Option<Instrument>,
+}
+
+// Load a groove by name from the grooves folder
+pub fn load_groove(name: &str) -> io::Result<Groove> {
+    let filename = format!("{}/{}.json", GROOVE_DIRECTORY, name);
+    let mut json = String::new();
+    File::open(&filename)?.read_to_string(&mut json)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+// Shift and accent notes according to the sixteenth they start on, keeping their length.
+// Notes are found with the song's pairing, so the groove moves the notes that would sound.
+pub fn apply_groove(packets: &[MidiPacket], groove: &Groove, instrument: Option<&Instrument>, pairing: NotePairing) -> Vec<MidiPacket> {
+    let mut notes = notes_from_packets_paired(packets, pairing);
+
+    for note in notes.iter_mut() {
+        if instrument.is_some_and(|instrument| *instrument != note.instrument) {
+            continue;
+        }
+
+        let step = (note.start.to_f64() * 4.0).round() as usize;
+        if !groove.timing.is_empty() {
+            let shift = Beats::from_f32(groove.timing[step % groove.timing.len()] / 4.0);
+            note.start = (note.start + shift).max(Beats::ZERO);
+        }
+        if !groove.velocity.is_empty() {
+            note.velocity *= groove.velocity[step % groove.velocity.len()];
+        }
+    }
+
+    packets_from_notes(&notes)
+}
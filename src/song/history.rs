@@ -0,0 +1,80 @@
+use serde::{Serialize, Deserialize};
+use std::fs::File;
+use std::io::{self, Write, Read};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::song::Song;
+
+// One entry of a song's revision log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Revision {
+    pub timestamp: u64,  // seconds since the Unix epoch
+    pub summary: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<Song>,  // the song as it was, if it was kept
+}
+
+impl Revision {
+    // The timestamp as "YYYY-MM-DD HH:MM UTC"
+    pub fn date(&self) -> String {
+        let days = (self.timestamp / 86400) as i64;
+        let minutes = self.timestamp % 86400 / 60;
+
+        // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, minutes / 60, minutes % 60)
+    }
+}
+
+// A song's revision history, kept next to it as <song>.history.json so composers get
+// basic versioning without needing git
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct History {
+    pub revisions: Vec<Revision>,
+}
+
+impl History {
+    // Add a revision dated now, with a snapshot of the song if one is given
+    pub fn record(&mut self, summary: &str, snapshot: Option<&Song>) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+        self.revisions.push(Revision {
+            timestamp,
+            summary: summary.to_string(),
+            snapshot: snapshot.cloned(),
+        });
+    }
+}
+
+pub fn history_path(song_filename: &str) -> String {
+    let path = Path::new(song_filename);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(song_filename);
+    path.with_file_name(format!("{}.history.json", stem)).to_string_lossy().into_owned()
+}
+
+// Save a song's history next to the song file
+pub fn save_history(history: &History, song_filename: &str) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(history)?;
+    let mut file = File::create(history_path(song_filename))?;
+    file.write_all(json.as_bytes())
+}
+
+// Load a song's history; a song without one has an empty history
+pub fn load_history(song_filename: &str) -> io::Result<History> {
+    let mut file = match File::open(history_path(song_filename)) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(History::default()),
+        Err(error) => return Err(error),
+    };
+    let mut json = String::new();
+    file.read_to_string(&mut json)?;
+    Ok(serde_json::from_str(&json)?)
+}
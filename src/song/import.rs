@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+use super::song::Song;
+use super::midi_file::MidiImporter;
+
+// Reads a song from one file format. Malformed input is an InvalidData error.
+pub trait SongImporter: Send + Sync {
+    fn import(&self, reader: &mut dyn Read) -> io::Result<Song>;
+}
+
+// Synthia's own song files
+pub struct JsonImporter;
+
+impl SongImporter for JsonImporter {
+    fn import(&self, reader: &mut dyn Read) -> io::Result<Song> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+static IMPORTERS: OnceLock<RwLock<BTreeMap<String, Arc<dyn SongImporter>>>> = OnceLock::new();
+
+fn importers() -> &'static RwLock<BTreeMap<String, Arc<dyn SongImporter>>> {
+    IMPORTERS.get_or_init(|| {
+        let built_in: [(&str, Arc<dyn SongImporter>); 3] = [("json", Arc::new(JsonImporter)), ("mid", Arc::new(MidiImporter)), ("midi", Arc::new(MidiImporter))];
+        RwLock::new(built_in.into_iter().map(|(extension, importer)| (extension.to_string(), importer)).collect())
+    })
+}
+
+// Make a format loadable from files ending in `.extension` (without the dot, any case)
+// Registering an existing extension replaces its importer, built-in ones included
+pub fn register_importer(extension: &str, importer: Arc<dyn SongImporter>) {
+    importers().write().unwrap().insert(extension.to_lowercase(), importer);
+}
+
+pub fn importer_for(extension: &str) -> Option<Arc<dyn SongImporter>> {
+    importers().read().unwrap().get(&extension.to_lowercase()).cloned()
+}
+
+pub fn importer_extensions() -> Vec<String> {
+    importers().read().unwrap().keys().cloned().collect()
+}
+
+impl Song {
+    // Load a song with the importer registered for the file's extension
+    // Songs from formats without a title are named after the file
+    pub fn load(filename: &str) -> io::Result<Song> {
+        let extension = Path::new(filename).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        let importer = importer_for(extension)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, format!("no importer for .{} files", extension)))?;
+
+        let mut reader = BufReader::new(File::open(filename)?);
+        let mut song = importer.import(&mut reader)?;
+        if song.songname.is_empty() {
+            song.songname = Path::new(filename).file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        }
+        Ok(song)
+    }
+}
@@ -1,10 +1,105 @@
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use super::registry::{registered_instrument, registered_instrument_names};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Instrument {
     Sine,
     Square,
     Triangle,
     Saw,
     Piano,
+    Custom(String),  // resolved through the instrument registry
+}
+
+const BUILT_IN: [(&str, Instrument); 5] = [
+    ("Sine", Instrument::Sine),
+    ("Square", Instrument::Square),
+    ("Triangle", Instrument::Triangle),
+    ("Saw", Instrument::Saw),
+    ("Piano", Instrument::Piano),
+];
+
+impl Instrument {
+    pub fn name(&self) -> &str {
+        match self {
+            Instrument::Custom(name) => name,
+            built_in => BUILT_IN.iter().find(|(_, instrument)| instrument == built_in).unwrap().0,
+        }
+    }
+
+    // MIDI pitches the instrument can play: the 88 keys for the piano, everything
+    // from C0 (the bottom of human hearing) up for the oscillators and plugins
+    pub fn range(&self) -> RangeInclusive<u8> {
+        match self {
+            Instrument::Piano => 21..=108,
+            _ => 12..=127,
+        }
+    }
+
+    // Move a pitch by whole octaves until it is inside the instrument's range
+    pub fn fold_into_range(&self, pitch: u8) -> u8 {
+        let range = self.range();
+        let mut pitch = pitch;
+        while pitch < *range.start() {
+            pitch += 12;
+        }
+        while pitch > *range.end() {
+            pitch -= 12;
+        }
+        pitch
+    }
+
+    // Every instrument name a song can currently use
+    pub fn available() -> Vec<String> {
+        let mut names: Vec<String> = BUILT_IN.iter().map(|(name, _)| name.to_string()).collect();
+        names.extend(registered_instrument_names());
+        names
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnknownInstrument(pub String);
+
+impl fmt::Display for UnknownInstrument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown instrument \"{}\", available instruments: {}", self.0, Instrument::available().join(", "))
+    }
+}
+
+impl std::error::Error for UnknownInstrument {}
+
+impl FromStr for Instrument {
+    type Err = UnknownInstrument;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some((_, instrument)) = BUILT_IN.iter().find(|(built_in, _)| *built_in == name) {
+            return Ok(instrument.clone());
+        }
+        match registered_instrument(name) {
+            Some(_) => Ok(Instrument::Custom(name.to_string())),
+            None => Err(UnknownInstrument(name.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Instrument {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Instrument {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
 }
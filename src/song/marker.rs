@@ -0,0 +1,9 @@
+use serde::{Serialize, Deserialize};
+use super::beats::Beats;
+
+// A named position in the song, like "chorus", that playback can start from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub name: String,
+    pub beat: Beats,
+}
@@ -0,0 +1,57 @@
+use super::beats::Beats;
+use super::note::{Note, notes_from_packets, packets_from_notes};
+use super::song::Song;
+
+// How much a longer note is preferred over a higher one: a note twice as long
+// competes as if it were this many semitones higher
+const DURATION_WEIGHT: f32 = 1.0;
+
+impl Song {
+    // A copy of the song keeping only its top line, e.g. for a lead sheet.
+    // At every point the highest sounding note is picked, with longer notes
+    // weighted up so short ornaments don't break up the melody. A note only
+    // plays from its own start: one that was covered and becomes the highest
+    // again later isn't played a second time.
+    pub fn extract_melody(&self) -> Song {
+        let notes = notes_from_packets(&self.mixed_packets());
+
+        let mut boundaries: Vec<Beats> = notes.iter().flat_map(|note| [note.start, note.start + note.duration]).collect();
+        boundaries.sort();
+        boundaries.dedup();
+
+        let mut melody: Vec<Note> = Vec::new();
+        let mut current: Option<usize> = None;
+
+        for window in boundaries.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            let top = notes
+                .iter()
+                .enumerate()
+                .filter(|(_, note)| note.start <= start && note.start + note.duration >= end)
+                .max_by(|(_, a), (_, b)| weight(a).total_cmp(&weight(b)))
+                .map(|(index, _)| index);
+
+            match top {
+                Some(index) if current == Some(index) => {
+                    melody.last_mut().unwrap().duration = end - melody.last().unwrap().start;
+                }
+                Some(index) if notes[index].start == start => {
+                    melody.push(Note { duration: end - start, ..notes[index].clone() });
+                    current = Some(index);
+                }
+                _ => current = None,
+            }
+        }
+
+        Song {
+            packets: packets_from_notes(&melody),
+            tracks: Vec::new(),
+            normalization_gain: None,
+            ..self.clone()
+        }
+    }
+}
+
+fn weight(note: &Note) -> f32 {
+    note.pitch as f32 + DURATION_WEIGHT * note.duration.to_f32().max(1e-3).log2()
+}
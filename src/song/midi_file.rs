@@ -0,0 +1,455 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use super::arrangement::flatten_packets;
+use super::beats::{Beats, gcd};
+use super::import::SongImporter;
+use super::instrument::Instrument;
+use super::midi_packet::MidiPacket;
+use super::note::{Note, notes_from_packets_paired};
+use super::note_status::NoteStatus;
+use super::pairing::NotePairing;
+use super::song::Song;
+use super::tempo::TempoChange;
+
+// General MIDI reserves channel 10 for drums, which no instrument can play
+const PERCUSSION_CHANNEL: u8 = 9;
+const DEFAULT_BPM: f32 = 120.0;
+// The usual release velocity for keyboards that don't measure it
+const RELEASE_VELOCITY: u8 = 64;
+// Used when the notes need a finer or odder grid than a MIDI file can store exactly
+const FALLBACK_TICKS_PER_BEAT: i64 = 960;
+
+// The instrument closest to a General MIDI program
+fn instrument_for_program(program: u8) -> Instrument {
+    match program {
+        0..=15 => Instrument::Piano,     // pianos and chromatic percussion
+        16..=23 => Instrument::Square,   // organs
+        24..=39 => Instrument::Triangle, // guitars and basses
+        40..=71 => Instrument::Saw,      // strings, ensembles, brass and reeds
+        72..=79 => Instrument::Sine,     // pipes
+        80 => Instrument::Square,        // square lead
+        81..=87 => Instrument::Saw,      // saw and other leads
+        _ => Instrument::Triangle,       // pads and effects
+    }
+}
+
+// The reverse of instrument_for_program: a program that imports as the same instrument
+fn program_for_instrument(instrument: &Instrument) -> u8 {
+    match instrument {
+        Instrument::Piano => 0,      // acoustic grand piano
+        Instrument::Triangle => 38,  // synth bass
+        Instrument::Sine => 73,      // flute
+        Instrument::Square => 80,    // square lead
+        Instrument::Saw => 81,       // saw lead
+        // Plugins have no General MIDI counterpart
+        Instrument::Custom(_) => 0,
+    }
+}
+
+// Read a Standard MIDI File (type 0 or 1) as a song. Notes keep their position in
+// beats; the first tempo becomes the song's bpm and later ones its tempo changes.
+// Program changes pick the instrument of a channel, and drums on channel 10 are left out.
+pub fn load_from_midi(filename: &str) -> io::Result<Song> {
+    parse_midi(&fs::read(filename)?)
+}
+
+// A timed note event from any track
+struct Event {
+    tick: u64,
+    status: NoteStatus,
+    pitch: u8,
+    velocity: f32,
+    instrument: Instrument,
+}
+
+pub fn parse_midi(data: &[u8]) -> io::Result<Song> {
+    let mut reader = ChunkReader { data, position: 0 };
+    let (id, header) = reader.chunk()?;
+    if id != b"MThd" || header.len() < 6 {
+        return Err(invalid("not a MIDI file"));
+    }
+    let format = u16::from_be_bytes([header[0], header[1]]);
+    let track_count = u16::from_be_bytes([header[2], header[3]]);
+    let division = u16::from_be_bytes([header[4], header[5]]);
+    if format > 1 {
+        return Err(invalid("only MIDI files of type 0 and 1 are supported"));
+    }
+    // The top bit selects SMPTE timing, which has no beats
+    if division == 0 || division & 0x8000 != 0 {
+        return Err(invalid("only MIDI files timed in ticks per beat are supported"));
+    }
+
+    let mut events = Vec::new();
+    let mut tempos = Vec::new();
+    let mut songname = String::new();
+    for track_index in 0..track_count {
+        let (id, track) = reader.chunk()?;
+        // Unknown chunks are allowed and skipped
+        if id != b"MTrk" {
+            continue;
+        }
+        let name = read_track(track, &mut events, &mut tempos)?;
+        if track_index == 0 {
+            songname = name.unwrap_or_default();
+        }
+    }
+
+    // Offs before Ons, so a note repeated without a gap isn't cut by its own Off
+    events.sort_by_key(|event| (event.tick, event.status == NoteStatus::On));
+    let mut tick = 0;
+    let packets = events
+        .into_iter()
+        .map(|event| {
+            let note_delta = Beats::new((event.tick - tick) as i64, division as i64);
+            tick = event.tick;
+            MidiPacket::new(event.pitch, event.instrument, event.status, note_delta, event.velocity)
+        })
+        .collect();
+
+    tempos.sort_by_key(|(tick, _)| *tick);
+    let mut song = Song::new(&songname, "", tempos.first().map_or(DEFAULT_BPM, |(_, bpm)| *bpm));
+    for &(tick, bpm) in tempos.iter().skip(1) {
+        let current = song.tempo_events.last().map_or(song.bpm, |change| change.bpm);
+        if bpm != current {
+            song.tempo_events.push(TempoChange { beat: Beats::new(tick as i64, division as i64), bpm });
+        }
+    }
+    song.packets = packets;
+    // MIDI releases the oldest of overlapping notes of the same pitch first
+    song.pairing = NotePairing::Fifo;
+    Ok(song)
+}
+
+// Collect the note events and tempos (with their tick) of one track, returning its name
+fn read_track(data: &[u8], events: &mut Vec<Event>, tempos: &mut Vec<(u64, f32)>) -> io::Result<Option<String>> {
+    let mut reader = ChunkReader { data, position: 0 };
+    let mut tick = 0u64;
+    let mut running_status = None;
+    let mut programs = [0u8; 16];
+    let mut name = None;
+
+    while reader.position < data.len() {
+        tick += reader.variable_length()? as u64;
+        let mut status = reader.byte()?;
+        // Running status: a data byte repeats the last channel message's status
+        if status < 0x80 {
+            status = running_status.ok_or_else(|| invalid("data byte without a status"))?;
+            reader.position -= 1;
+        }
+
+        match status {
+            0xFF => {
+                running_status = None;
+                let kind = reader.byte()?;
+                let length = reader.variable_length()? as usize;
+                let meta = reader.bytes(length)?;
+                match (kind, meta) {
+                    (0x2F, _) => break,  // end of track
+                    (0x51, &[a, b, c]) => {
+                        let microseconds_per_beat = u32::from_be_bytes([0, a, b, c]);
+                        if microseconds_per_beat > 0 {
+                            tempos.push((tick, 60_000_000.0 / microseconds_per_beat as f32));
+                        }
+                    }
+                    (0x03, name_bytes) if name.is_none() => name = Some(String::from_utf8_lossy(name_bytes).trim().to_string()),
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                running_status = None;
+                let length = reader.variable_length()? as usize;
+                reader.bytes(length)?;
+            }
+            0x80..=0xEF => {
+                running_status = Some(status);
+                let channel = status & 0x0F;
+                match status & 0xF0 {
+                    0x80 | 0x90 => {
+                        let pitch = reader.byte()? & 0x7F;
+                        let velocity = reader.byte()? & 0x7F;
+                        if channel == PERCUSSION_CHANNEL {
+                            continue;
+                        }
+                        // A note-on with velocity 0 is a note-off
+                        let note_status = if status & 0xF0 == 0x90 && velocity > 0 { NoteStatus::On } else { NoteStatus::Off };
+                        events.push(Event {
+                            tick,
+                            status: note_status,
+                            pitch,
+                            velocity: velocity as f32 / 127.0,
+                            instrument: instrument_for_program(programs[channel as usize]),
+                        });
+                    }
+                    0xC0 => programs[channel as usize] = reader.byte()? & 0x7F,
+                    0xD0 => {
+                        reader.byte()?;
+                    }
+                    _ => {
+                        reader.bytes(2)?;
+                    }
+                }
+            }
+            // System common and real-time messages have no place in a file
+            _ => return Err(invalid("unexpected system message")),
+        }
+    }
+
+    Ok(name)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// Bounds-checked reading, so truncated files are errors instead of panics
+struct ChunkReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ChunkReader<'a> {
+    fn bytes(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let end = self.position.checked_add(length).filter(|&end| end <= self.data.len()).ok_or_else(|| invalid("MIDI file is truncated"))?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // Up to four bytes of seven bits each, most significant first
+    fn variable_length(&mut self) -> io::Result<u32> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("variable-length number is too long"))
+    }
+
+    fn chunk(&mut self) -> io::Result<(&'a [u8], &'a [u8])> {
+        let id = self.bytes(4)?;
+        let length = u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()) as usize;
+        Ok((id, self.bytes(length)?))
+    }
+}
+
+// Write a song as it plays (repeats, dynamics, grooves and all) to a type 1 Standard
+// MIDI File, with a track per instrument on channels of their own
+pub fn save_to_midi(song: &Song, filename: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    write_midi(song, &mut writer)?;
+    writer.flush()
+}
+
+pub fn write_midi<W: Write>(song: &Song, mut writer: W) -> io::Result<()> {
+    if !song.bpm.is_finite() || song.bpm <= 0.0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("can't write a tempo of {} bpm", song.bpm)));
+    }
+    let packets = flatten_packets(song);
+    // MIDI files pair notes first in, first out; writing every note with an Off of its
+    // own keeps the song's pairing
+    let notes = notes_from_packets_paired(&packets, song.pairing);
+    let end: Beats = packets.iter().map(|packet| packet.note_delta).sum();
+
+    // Exact when every note falls on a grid a MIDI file can hold, rounded otherwise
+    let mut tempo_changes: Vec<&TempoChange> = song.tempo_events.iter().filter(|change| change.bpm.is_finite() && change.bpm > 0.0).collect();
+    tempo_changes.sort_by_key(|change| change.beat);
+    let ticks_per_beat = notes
+        .iter()
+        .flat_map(|note| [note.start, note.start + note.duration])
+        .chain(tempo_changes.iter().map(|change| change.beat))
+        .chain([end])
+        .try_fold(1i128, |grid, position| {
+            let grid = grid / gcd(grid, position.denominator() as i128) * position.denominator() as i128;
+            (grid <= 0x7FFF).then_some(grid)
+        })
+        .map_or(FALLBACK_TICKS_PER_BEAT, |grid| grid as i64);
+    let to_ticks = |position: Beats| (position.to_f64() * ticks_per_beat as f64).round().max(0.0) as u64;
+
+    let mut instruments: Vec<&Instrument> = Vec::new();
+    for note in &notes {
+        if !instruments.contains(&&note.instrument) {
+            instruments.push(&note.instrument);
+        }
+    }
+    let channels: Vec<u8> = (0..16).filter(|&channel| channel != PERCUSSION_CHANNEL).collect();
+    if instruments.len() > channels.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("a MIDI file has room for {} instruments, the song has {}", channels.len(), instruments.len())));
+    }
+
+    let mut tempo_track = Vec::new();
+    write_meta(&mut tempo_track, 0, 0x03, song.songname.as_bytes());
+    let mut tick = 0;
+    for (event_tick, bpm) in [(0, song.bpm)].into_iter().chain(tempo_changes.iter().map(|change| (to_ticks(change.beat), change.bpm))) {
+        let microseconds_per_beat = (60_000_000.0 / bpm).round().clamp(1.0, 0xFF_FFFF as f32) as u32;
+        write_meta(&mut tempo_track, (event_tick - tick).min(0x0FFF_FFFF) as u32, 0x51, &microseconds_per_beat.to_be_bytes()[1..]);
+        tick = event_tick;
+    }
+    // The tempo track lasts as long as the song, keeping any rest at its end
+    write_meta(&mut tempo_track, to_ticks(end).saturating_sub(tick).min(0x0FFF_FFFF) as u32, 0x2F, &[]);
+
+    let mut tracks = vec![tempo_track];
+    for (instrument, &channel) in instruments.iter().zip(&channels) {
+        let mut track = Vec::new();
+        write_meta(&mut track, 0, 0x03, instrument.name().as_bytes());
+        write_variable_length(&mut track, 0);
+        track.extend([0xC0 | channel, program_for_instrument(instrument)]);
+
+        let mut events: Vec<(u64, NoteStatus, &Note)> = Vec::new();
+        for note in notes.iter().filter(|note| &note.instrument == *instrument) {
+            events.push((to_ticks(note.start), NoteStatus::On, note));
+            events.push((to_ticks(note.start + note.duration), NoteStatus::Off, note));
+        }
+        events.sort_by_key(|(tick, status, _)| (*tick, *status == NoteStatus::On));
+
+        let mut tick = 0;
+        for (event_tick, status, note) in events {
+            write_variable_length(&mut track, (event_tick - tick).min(0x0FFF_FFFF) as u32);
+            tick = event_tick;
+            let (status, velocity) = match status {
+                NoteStatus::On => (0x90, (note.velocity * 127.0).round().clamp(1.0, 127.0) as u8),
+                NoteStatus::Off => (0x80, RELEASE_VELOCITY),
+            };
+            track.extend([status | channel, note.pitch & 0x7F, velocity]);
+        }
+        write_meta(&mut track, 0, 0x2F, &[]);
+        tracks.push(track);
+    }
+
+    write_chunk(&mut writer, b"MThd", &[&1u16.to_be_bytes()[..], &(tracks.len() as u16).to_be_bytes(), &(ticks_per_beat as u16).to_be_bytes()].concat())?;
+    for track in &tracks {
+        write_chunk(&mut writer, b"MTrk", track)?;
+    }
+    Ok(())
+}
+
+fn write_chunk<W: Write>(writer: &mut W, id: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(id)?;
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(data)
+}
+
+fn write_meta(track: &mut Vec<u8>, delta: u32, kind: u8, data: &[u8]) {
+    write_variable_length(track, delta);
+    track.extend([0xFF, kind]);
+    write_variable_length(track, data.len() as u32);
+    track.extend(data);
+}
+
+// Seven bits per byte, most significant first, the high bit set on all but the last
+fn write_variable_length(track: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    track.extend(bytes.iter().rev());
+}
+
+// Standard MIDI Files, for Song::load
+pub struct MidiImporter;
+
+impl SongImporter for MidiImporter {
+    fn import(&self, reader: &mut dyn Read) -> io::Result<Song> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        parse_midi(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::{notes_from_packets, packets_from_notes};
+
+    fn note(start: Beats, duration: Beats, pitch: u8, instrument: Instrument, velocity: u8) -> Note {
+        Note {
+            start,
+            duration,
+            pitch,
+            instrument,
+            velocity: velocity as f32 / 127.0,
+            articulations: Vec::new(),
+            envelope: None,
+            pan: 0.0,
+        }
+    }
+
+    fn sorted_notes(song: &Song) -> Vec<Note> {
+        let mut notes = notes_from_packets(&song.packets);
+        notes.sort_by(|a, b| a.start.cmp(&b.start).then(a.pitch.cmp(&b.pitch)));
+        notes
+    }
+
+    fn write(song: &Song) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_midi(song, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trips_notes_tempos_and_name() {
+        let mut song = Song::new("Round Trip", "", 120.0);
+        song.packets = packets_from_notes(&[
+            note(Beats::ZERO, Beats::whole(1), 60, Instrument::Piano, 100),
+            note(Beats::new(1, 3), Beats::new(2, 3), 64, Instrument::Piano, 80),
+            note(Beats::whole(1), Beats::new(3, 4), 43, Instrument::Saw, 127),
+            note(Beats::whole(4), Beats::whole(2), 67, Instrument::Piano, 1),
+        ]);
+        song.tempo_events = vec![TempoChange { beat: Beats::whole(2), bpm: 96.0 }, TempoChange { beat: Beats::whole(5), bpm: 150.0 }];
+
+        let parsed = parse_midi(&write(&song)).unwrap();
+        assert_eq!(parsed.songname, "Round Trip");
+        assert_eq!(parsed.bpm, 120.0);
+        assert_eq!(parsed.tempo_events, song.tempo_events);
+        assert_eq!(sorted_notes(&parsed), sorted_notes(&song));
+    }
+
+    #[test]
+    fn reads_running_status_and_velocity_zero_note_offs() {
+        let track = [
+            0x00, 0x90, 60, 100,  // On C4
+            0x60, 60, 0,          // running status, velocity 0: Off after a beat
+            0x00, 64, 100,        // On E4
+            0x60, 64, 0,          // Off
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let data = [&b"MThd"[..], &[0, 0, 0, 6, 0, 0, 0, 1, 0, 96], b"MTrk", &(track.len() as u32).to_be_bytes(), &track].concat();
+
+        let song = parse_midi(&data).unwrap();
+        assert_eq!(song.bpm, DEFAULT_BPM);
+        assert_eq!(
+            sorted_notes(&song),
+            vec![
+                note(Beats::ZERO, Beats::whole(1), 60, Instrument::Piano, 100),
+                note(Beats::whole(1), Beats::whole(1), 64, Instrument::Piano, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_files_are_invalid_data() {
+        let mut song = Song::new("Truncated", "", 120.0);
+        song.packets = packets_from_notes(&[note(Beats::ZERO, Beats::whole(1), 60, Instrument::Sine, 100)]);
+        let data = write(&song);
+        assert!(parse_midi(&data).is_ok());
+        for length in 0..data.len() {
+            let error = parse_midi(&data[..length]).err().unwrap_or_else(|| panic!("{} bytes parsed", length));
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{} bytes", length);
+        }
+    }
+
+    #[test]
+    fn data_byte_without_a_status_is_invalid_data() {
+        let track = [0x00, 60, 100];
+        let data = [&b"MThd"[..], &[0, 0, 0, 6, 0, 0, 0, 1, 0, 96], b"MTrk", &(track.len() as u32).to_be_bytes(), &track].concat();
+        assert_eq!(parse_midi(&data).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
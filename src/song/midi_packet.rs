@@ -1,12 +1,56 @@
 use serde::{Serialize, Deserialize};
 use super::instrument::Instrument;
 use super::note_status::NoteStatus;
+use super::arrangement::TriggerCondition;
+use super::beats::Beats;
+use super::articulation::Articulation;
+use super::envelope::Envelope;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MidiPacket {
+    #[serde(with = "super::pitch")]
     pub pitch: u8,
     pub instrument: Instrument,
     pub note_status: NoteStatus,
-    pub note_delta: f32,
+    pub note_delta: Beats,
     pub velocity: f32,
+    #[serde(default = "default_probability", skip_serializing_if = "is_certain")]
+    pub probability: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<TriggerCondition>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub articulations: Vec<Articulation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<Envelope>,  // replaces the instrument's envelope for this note
+    #[serde(default, skip_serializing_if = "is_center")]
+    pub pan: f32,                    // -1 is left, 0 center, 1 right
+}
+
+impl MidiPacket {
+    pub fn new(pitch: u8, instrument: Instrument, note_status: NoteStatus, note_delta: Beats, velocity: f32) -> Self {
+        MidiPacket {
+            pitch,
+            instrument,
+            note_status,
+            note_delta,
+            velocity,
+            probability: default_probability(),
+            condition: None,
+            articulations: Vec::new(),
+            envelope: None,
+            pan: 0.0,
+        }
+    }
+}
+
+fn default_probability() -> f32 {
+    1.0
+}
+
+fn is_certain(probability: &f32) -> bool {
+    *probability >= 1.0
+}
+
+fn is_center(pan: &f32) -> bool {
+    *pan == 0.0
 }
@@ -1,9 +1,68 @@
 mod instrument;
+mod registry;
 mod note_status;
 mod midi_packet;
+mod beats;
+mod pitch;
+#[allow(clippy::module_inception)]
 mod song;
+mod playlist;
+mod modulation;
+mod variation;
+mod arrangement;
+mod dynamics;
+mod note;
+mod template;
+mod groove;
+mod validation;
+mod canonical;
+mod session;
+mod mono;
+mod marker;
+mod analysis;
+mod melody;
+mod morph;
+mod history;
+mod pairing;
+mod drift;
+mod import;
+mod articulation;
+mod release;
+mod midi_file;
+mod envelope;
+mod track;
+mod tempo;
+mod effect;
 
-pub use instrument::Instrument;
+pub use instrument::{Instrument, UnknownInstrument};
+pub use registry::{InstrumentRenderer, register_instrument, unregister_instrument, registered_instrument};
 pub use note_status::NoteStatus;
 pub use midi_packet::MidiPacket;
-pub use song::{Song, save_to_json, load_from_json};
+pub use articulation::Articulation;
+pub use envelope::{Envelope, InstrumentEnvelope};
+pub use beats::Beats;
+pub use pitch::PitchFormat;
+pub use song::{Song, save_to_json, save_normalization_gain, load_from_json, load_from_str};
+pub use modulation::{Lfo, LfoShape, LfoRate, ModulationRoute, ModulationTarget};
+pub use variation::{Variation, PhaseMode};
+pub use arrangement::{TriggerCondition, flatten_packets};
+pub use dynamics::{Dynamic, DynamicLevel, dynamics_scale};
+pub use note::{Note, packets_from_notes, notes_from_packets, notes_from_packets_paired};
+pub use pairing::{NotePairing, pair_notes};
+pub use template::{TEMPLATES, song_from_template};
+pub use groove::{Groove, GrooveAssignment, load_groove, apply_groove, GROOVE_DIRECTORY};
+pub use validation::song_warnings;
+pub use mono::{MonoMode, NotePriority, apply_mono};
+pub use marker::Marker;
+pub use morph::Morph;
+pub use drift::Drift;
+pub use release::ReleaseLayer;
+pub use track::Track;
+pub use tempo::{TempoChange, TempoMap, MAX_SONG_SECONDS};
+pub use effect::{EffectSettings, MAX_DELAY_TIME};
+pub use midi_file::{load_from_midi, parse_midi, save_to_midi, write_midi, MidiImporter};
+pub use import::{SongImporter, JsonImporter, register_importer, importer_for, importer_extensions};
+pub use history::{Revision, History, history_path, save_history, load_history};
+pub use analysis::{Analysis, Chord, ChordQuality, Key, KeyMode, analyze_song, detect_chord, detect_key};
+pub use session::{Session, save_session, load_session};
+pub use playlist::{Playlist, load_playlist, is_playlist};
\ No newline at end of file
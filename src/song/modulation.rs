@@ -0,0 +1,36 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Random,
+}
+
+// LFO speed, either absolute or as the length of one cycle in beats
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LfoRate {
+    Hz(f32),
+    Beats(f32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Lfo {
+    pub name: String,
+    pub shape: LfoShape,
+    pub rate: LfoRate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum ModulationTarget {
+    Pitch,      // depth in semitones
+    Amplitude,  // depth from 0 (no effect) to 1 (full tremolo)
+}
+
+// One entry of the modulation matrix: which LFO drives which target, and how much
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModulationRoute {
+    pub lfo: String,
+    pub target: ModulationTarget,
+    pub depth: f32,
+}
@@ -0,0 +1,86 @@
+use serde::{Serialize, Deserialize};
+use super::instrument::Instrument;
+use super::midi_packet::MidiPacket;
+use super::beats::Beats;
+use super::note::{Note, notes_from_packets_paired, packets_from_notes};
+use super::pairing::NotePairing;
+
+// Which of the held notes a mono voice plays
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum NotePriority {
+    #[default]
+    Last,
+    Highest,
+    Lowest,
+}
+
+// Play an instrument on a single voice, so overlapping notes don't stack.
+// The voice plays one of the held notes, chosen by priority, and either restarts
+// on every change or, in legato mode, slides the sounding voice to the new pitch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonoMode {
+    pub instrument: Instrument,
+    #[serde(default = "default_retrigger")]
+    pub retrigger: bool,  // false for legato
+    #[serde(default)]
+    pub glide: f32,       // legato pitch slide time in seconds, 0 jumps straight to the new pitch
+    #[serde(default)]
+    pub priority: NotePriority,
+}
+
+fn default_retrigger() -> bool {
+    true
+}
+
+// Replace the notes of the mode's instrument with what a single voice plays.
+// Whenever a note starts or ends, the voice switches to the held note with the
+// highest priority, so releasing a note returns to one that is still held.
+// Notes are found with the song's pairing.
+pub fn apply_mono(packets: &[MidiPacket], mode: &MonoMode, pairing: NotePairing) -> Vec<MidiPacket> {
+    let (held_notes, mut notes): (Vec<Note>, Vec<Note>) = notes_from_packets_paired(packets, pairing)
+        .into_iter()
+        .partition(|note| note.instrument == mode.instrument);
+
+    // Note starts and ends in time order, ends first. Notes with no length never sound.
+    let mut events: Vec<(Beats, bool, usize)> = held_notes
+        .iter()
+        .enumerate()
+        .filter(|(_, note)| note.duration > Beats::ZERO)
+        .flat_map(|(index, note)| [(note.start, true, index), (note.start + note.duration, false, index)])
+        .collect();
+    events.sort();
+
+    let mut held: Vec<usize> = Vec::new();
+    let mut sounding: Option<(usize, Beats)> = None;
+
+    for (event_index, &(time, starts, index)) in events.iter().enumerate() {
+        if starts {
+            held.push(index);
+        } else {
+            held.retain(|&held_index| held_index != index);
+        }
+
+        // Only pick a note once every event at this time has been applied
+        if events.get(event_index + 1).is_some_and(|next| next.0 == time) {
+            continue;
+        }
+
+        let chosen = match mode.priority {
+            NotePriority::Last => held.last().copied(),
+            NotePriority::Highest => held.iter().copied().max_by_key(|&index| held_notes[index].pitch),
+            NotePriority::Lowest => held.iter().copied().min_by_key(|&index| held_notes[index].pitch),
+        };
+        if chosen == sounding.map(|(index, _)| index) {
+            continue;
+        }
+
+        if let Some((index, start)) = sounding {
+            if time > start {
+                notes.push(Note { start, duration: time - start, ..held_notes[index].clone() });
+            }
+        }
+        sounding = chosen.map(|index| (index, time));
+    }
+
+    packets_from_notes(&notes)
+}
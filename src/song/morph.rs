@@ -0,0 +1,14 @@
+use serde::{Serialize, Deserialize};
+use super::beats::Beats;
+use super::instrument::Instrument;
+
+// Gradually turn one instrument into another: notes sound like `instrument` before
+// `start`, like `to` after `end`, and crossfade between the two in between.
+// Only the first morph of an instrument is used.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Morph {
+    pub instrument: Instrument,
+    pub to: Instrument,
+    pub start: Beats,
+    pub end: Beats,
+}
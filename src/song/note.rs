@@ -0,0 +1,86 @@
+use super::instrument::Instrument;
+use super::midi_packet::MidiPacket;
+use super::note_status::NoteStatus;
+use super::beats::Beats;
+use super::pairing::{NotePairing, pair_notes};
+use super::articulation::Articulation;
+use super::envelope::Envelope;
+
+// A note with absolute timing in beats, easier to generate and edit than
+// the delta-coded On/Off packets songs are stored as
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub start: Beats,
+    pub duration: Beats,
+    pub pitch: u8,
+    pub instrument: Instrument,
+    pub velocity: f32,
+    pub articulations: Vec<Articulation>,
+    pub envelope: Option<Envelope>,
+    pub pan: f32,
+}
+
+// Convert notes into delta-coded packets, ordered by time with Offs before Ons
+pub fn packets_from_notes(notes: &[Note]) -> Vec<MidiPacket> {
+    let mut events: Vec<(Beats, NoteStatus, &Note)> = Vec::with_capacity(notes.len() * 2);
+    for note in notes {
+        events.push((note.start, NoteStatus::On, note));
+        events.push((note.start + note.duration, NoteStatus::Off, note));
+    }
+    events.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then((a.1 == NoteStatus::On).cmp(&(b.1 == NoteStatus::On)))
+            .then(a.2.pitch.cmp(&b.2.pitch))
+    });
+
+    let mut beat = Beats::ZERO;
+    events
+        .into_iter()
+        .map(|(time, note_status, note)| {
+            let note_delta = time - beat;
+            beat = time;
+            let mut packet = MidiPacket::new(note.pitch, note.instrument.clone(), note_status, note_delta, note.velocity);
+            if packet.note_status == NoteStatus::On {
+                packet.articulations = note.articulations.clone();
+                packet.envelope = note.envelope;
+                packet.pan = note.pan;
+            }
+            packet
+        })
+        .collect()
+}
+
+// Pair each On with the next Off of the same pitch and instrument, like the renderer does by default
+// Notes without a matching Off are dropped
+pub fn notes_from_packets(packets: &[MidiPacket]) -> Vec<Note> {
+    notes_from_packets_paired(packets, NotePairing::Strict)
+}
+
+// Same as notes_from_packets, matching Ons and Offs with the given strategy
+pub fn notes_from_packets_paired(packets: &[MidiPacket], pairing: NotePairing) -> Vec<Note> {
+    let positions: Vec<Beats> = packets
+        .iter()
+        .scan(Beats::ZERO, |position, packet| {
+            *position += packet.note_delta;
+            Some(*position)
+        })
+        .collect();
+
+    pair_notes(packets, pairing)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, off)| {
+            let packet = &packets[index];
+            off.map(|off| Note {
+                start: positions[index],
+                duration: positions[off] - positions[index],
+                pitch: packet.pitch,
+                instrument: packet.instrument.clone(),
+                velocity: packet.velocity,
+                articulations: packet.articulations.clone(),
+                envelope: packet.envelope,
+                pan: packet.pan,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,55 @@
+use serde::{Serialize, Deserialize};
+use super::midi_packet::MidiPacket;
+use super::note_status::NoteStatus;
+
+// How an On packet finds the Off that ends it. All strategies only pair packets of the
+// same pitch and instrument; they differ in which note an Off ends when a pitch is
+// struck again before it was released.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum NotePairing {
+    // Every On ends at the next Off, so overlapping repeats all end together
+    #[default]
+    Strict,
+    // Each Off ends the oldest held note
+    Fifo,
+    // Each Off ends the most recently struck note
+    Lifo,
+}
+
+// For every packet, the index of the Off that ends it.
+// None for Offs and for notes that are never released.
+pub fn pair_notes(packets: &[MidiPacket], pairing: NotePairing) -> Vec<Option<usize>> {
+    let mut offs = vec![None; packets.len()];
+    let same_key = |a: &MidiPacket, b: &MidiPacket| a.pitch == b.pitch && a.instrument == b.instrument;
+
+    if pairing == NotePairing::Strict {
+        for (index, packet) in packets.iter().enumerate() {
+            if packet.note_status == NoteStatus::On {
+                offs[index] = (index + 1..packets.len())
+                    .find(|&next| packets[next].note_status == NoteStatus::Off && same_key(&packets[next], packet));
+            }
+        }
+        return offs;
+    }
+
+    // Notes struck but not yet released, oldest first
+    let mut held: Vec<usize> = Vec::new();
+    for (index, packet) in packets.iter().enumerate() {
+        match packet.note_status {
+            NoteStatus::On => held.push(index),
+            NoteStatus::Off => {
+                let mut matching = held.iter().enumerate().filter(|(_, &on)| same_key(&packets[on], packet));
+                let position = match pairing {
+                    NotePairing::Lifo => matching.next_back(),
+                    _ => matching.next(),
+                };
+                // An Off with nothing held is ignored
+                if let Some((position, _)) = position {
+                    offs[held.remove(position)] = Some(index);
+                }
+            }
+        }
+    }
+
+    offs
+}
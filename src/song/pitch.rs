@@ -0,0 +1,64 @@
+use serde::{Serializer, Deserializer, de};
+use std::cell::Cell;
+use std::fmt;
+use crate::units::{note_name, parse_note_name};
+
+// How MidiPacket pitches are written when saving songs
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PitchFormat {
+    Number,  // "pitch": 61
+    Name,    // "pitch": "C#4"
+}
+
+thread_local! {
+    // Only set while a save asks for another format, everything else writes numbers
+    static WRITE_FORMAT: Cell<PitchFormat> = const { Cell::new(PitchFormat::Number) };
+}
+
+// Run `write` with pitches serialized in the given format
+pub(crate) fn with_pitch_format<T>(format: PitchFormat, write: impl FnOnce() -> T) -> T {
+    let previous = WRITE_FORMAT.replace(format);
+    let result = write();
+    WRITE_FORMAT.set(previous);
+    result
+}
+
+// serde helpers for MidiPacket::pitch: reads MIDI numbers or note names, writes numbers
+// unless inside with_pitch_format
+pub fn serialize<S: Serializer>(pitch: &u8, serializer: S) -> Result<S::Ok, S::Error> {
+    match WRITE_FORMAT.get() {
+        PitchFormat::Number => serializer.serialize_u8(*pitch),
+        PitchFormat::Name => serializer.serialize_str(&note_name(*pitch)),
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    deserializer.deserialize_any(PitchVisitor)
+}
+
+struct PitchVisitor;
+
+impl de::Visitor<'_> for PitchVisitor {
+    type Value = u8;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a MIDI note number from 0 to 127 or a note name like \"C#4\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<u8, E> {
+        u8::try_from(value)
+            .ok()
+            .filter(|pitch| *pitch <= 127)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<u8, E> {
+        u64::try_from(value)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+            .and_then(|value| self.visit_u64(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<u8, E> {
+        parse_note_name(value).ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Serialize, Deserialize};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use super::song::Song;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Playlist {
+    pub songs: Vec<String>,
+}
+
+impl Playlist {
+    // Load every song of the playlist, in order, in any format Song::load reads.
+    // The error of a song that can't be loaded names its file.
+    pub fn load_songs(&self) -> io::Result<Vec<Song>> {
+        self.songs
+            .iter()
+            .map(|filename| Song::load(filename).map_err(|error| io::Error::new(error.kind(), format!("{}: {}", filename, error))))
+            .collect()
+    }
+}
+
+// Load a playlist from an .m3u file or a JSON file of the form {"songs": [...]}
+// Relative song paths are resolved against the playlist's directory
+pub fn load_playlist(filename: &str) -> io::Result<Playlist> {
+    let mut file = File::open(filename)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    let entries: Vec<String> = if filename.ends_with(".m3u") || filename.ends_with(".m3u8") {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect()
+    } else {
+        serde_json::from_str::<Playlist>(&contents)?.songs
+    };
+
+    let base = Path::new(filename).parent().unwrap_or(Path::new(""));
+    let songs = entries
+        .into_iter()
+        .map(|entry| base.join(entry).to_string_lossy().into_owned())
+        .collect();
+
+    Ok(Playlist { songs })
+}
+
+// Whether a file looks like a playlist rather than a single song
+pub fn is_playlist(filename: &str) -> bool {
+    if filename.ends_with(".m3u") || filename.ends_with(".m3u8") {
+        return true;
+    }
+
+    let mut contents = String::new();
+    match File::open(filename).and_then(|mut file| file.read_to_string(&mut contents)) {
+        Ok(_) => serde_json::from_str::<Playlist>(&contents).is_ok(),
+        Err(_) => false,
+    }
+}
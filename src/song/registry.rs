@@ -0,0 +1,29 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+// Renders one sample of a note: (frequency in Hz, time since note start in seconds) -> sample
+pub type InstrumentRenderer = Arc<dyn Fn(f32, f32) -> f32 + Send + Sync>;
+
+static REGISTRY: OnceLock<RwLock<BTreeMap<String, InstrumentRenderer>>> = OnceLock::new();
+
+fn registry() -> &'static RwLock<BTreeMap<String, InstrumentRenderer>> {
+    REGISTRY.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+// Make an instrument available to songs under `name`
+// Registering an existing name replaces it; built-in names can't be overridden
+pub fn register_instrument(name: &str, renderer: InstrumentRenderer) {
+    registry().write().unwrap().insert(name.to_string(), renderer);
+}
+
+pub fn unregister_instrument(name: &str) {
+    registry().write().unwrap().remove(name);
+}
+
+pub fn registered_instrument(name: &str) -> Option<InstrumentRenderer> {
+    registry().read().unwrap().get(name).cloned()
+}
+
+pub fn registered_instrument_names() -> Vec<String> {
+    registry().read().unwrap().keys().cloned().collect()
+}
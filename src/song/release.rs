@@ -0,0 +1,23 @@
+use serde::{Serialize, Deserialize};
+use super::instrument::Instrument;
+
+// A short sound played when a note of an instrument is released, like a piano's damper
+// falling back or a bow leaving the string. Filtered noise by default, or a WAV sample.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReleaseLayer {
+    pub instrument: Instrument,
+    #[serde(default = "default_level_db")]
+    pub level_db: f32,           // relative to the note, at full velocity
+    #[serde(default = "default_length")]
+    pub length: f32,             // seconds the noise takes to die away
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<String>,  // WAV file played instead of the noise
+}
+
+fn default_level_db() -> f32 {
+    -30.0
+}
+
+fn default_length() -> f32 {
+    0.08
+}
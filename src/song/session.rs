@@ -0,0 +1,94 @@
+use serde::{Serialize, Deserialize};
+use std::fs::File;
+use std::io::{self, Write, Read};
+use super::beats::Beats;
+use super::midi_packet::MidiPacket;
+use super::note::{Note, notes_from_packets, packets_from_notes};
+use super::song::Song;
+use super::track::Track;
+
+// A workspace holding several songs, e.g. an album, saved as one file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Session {
+    pub name: String,
+    pub songs: Vec<Song>,
+}
+
+impl Session {
+    pub fn new(name: &str) -> Self {
+        Session { name: name.to_string(), songs: Vec::new() }
+    }
+
+    pub fn song(&self, songname: &str) -> Option<&Song> {
+        self.songs.iter().find(|song| song.songname == songname)
+    }
+
+    pub fn song_mut(&mut self, songname: &str) -> Option<&mut Song> {
+        self.songs.iter_mut().find(|song| song.songname == songname)
+    }
+
+    // Copy the notes starting in [start, end) of one song into another at `at`,
+    // keeping them alongside the notes already there (the destination's packets are rebuilt
+    // from its notes). Each track's notes go to the destination track with the same name,
+    // which is created if it's missing. Returns false if either song is missing.
+    pub fn copy_region(&mut self, from: &str, start: Beats, end: Beats, to: &str, at: Beats) -> bool {
+        let (copied, tracks) = match self.song(from) {
+            Some(song) => (
+                region_notes(&song.packets, start, end, at),
+                song.tracks
+                    .iter()
+                    .map(|track| (track.clone(), region_notes(&track.packets, start, end, at)))
+                    .filter(|(_, notes)| !notes.is_empty())
+                    .collect::<Vec<_>>(),
+            ),
+            None => return false,
+        };
+
+        match self.song_mut(to) {
+            Some(song) => {
+                add_notes(&mut song.packets, copied);
+                for (track, notes) in tracks {
+                    match song.tracks.iter_mut().find(|existing| existing.name == track.name) {
+                        Some(existing) => add_notes(&mut existing.packets, notes),
+                        None => song.tracks.push(Track { packets: packets_from_notes(&notes), ..track }),
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// The notes starting in [start, end), moved so the region starts at `at`
+fn region_notes(packets: &[MidiPacket], start: Beats, end: Beats, at: Beats) -> Vec<Note> {
+    notes_from_packets(packets)
+        .into_iter()
+        .filter(|note| note.start >= start && note.start < end)
+        .map(|mut note| {
+            note.start = note.start - start + at;
+            note
+        })
+        .collect()
+}
+
+fn add_notes(packets: &mut Vec<MidiPacket>, copied: Vec<Note>) {
+    let mut notes = notes_from_packets(packets);
+    notes.extend(copied);
+    *packets = packets_from_notes(&notes);
+}
+
+// Save session to a JSON file
+pub fn save_session(session: &Session, filename: &str) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(session)?;
+    let mut file = File::create(filename)?;
+    file.write_all(json.as_bytes())
+}
+
+// Load session from a JSON file
+pub fn load_session(filename: &str) -> io::Result<Session> {
+    let mut file = File::open(filename)?;
+    let mut json = String::new();
+    file.read_to_string(&mut json)?;
+    Ok(serde_json::from_str(&json)?)
+}
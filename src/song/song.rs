@@ -1,21 +1,130 @@
 use serde::{Serialize, Deserialize};
 use std::fs::File;
-use std::io::{Write, Read};
+use std::io::{self, Write, Read};
 use super::midi_packet::MidiPacket;
+use super::modulation::{Lfo, ModulationRoute};
+use super::variation::Variation;
+use super::dynamics::Dynamic;
+use super::groove::GrooveAssignment;
+use super::mono::MonoMode;
+use super::marker::Marker;
+use super::morph::Morph;
+use super::drift::Drift;
+use super::release::ReleaseLayer;
+use super::envelope::InstrumentEnvelope;
+use super::pairing::NotePairing;
+use super::pitch::{PitchFormat, with_pitch_format};
+use super::track::Track;
+use super::tempo::TempoChange;
+use super::effect::EffectSettings;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Song {
     pub songname: String,
     pub artist: String,
     pub bpm: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tempo_events: Vec<TempoChange>,  // tempo changes after the start
     pub packets: Vec<MidiPacket>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracks: Vec<Track>,  // played together with the packets
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lfos: Vec<Lfo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modulations: Vec<ModulationRoute>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variations: Vec<Variation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dynamics: Vec<Dynamic>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grooves: Vec<GrooveAssignment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mono: Vec<MonoMode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub markers: Vec<Marker>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub morphs: Vec<Morph>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub drifts: Vec<Drift>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub releases: Vec<ReleaseLayer>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub envelopes: Vec<InstrumentEnvelope>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<EffectSettings>,  // applied to the mix, in order
+    #[serde(default = "default_repeat", skip_serializing_if = "is_single_pass")]
+    pub repeat: u32,  // how many times the packet list is played
+    #[serde(default, skip_serializing_if = "is_default_seed")]
+    pub seed: u64,    // seed for note probabilities
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fold_octaves: bool,  // move notes outside an instrument's range into it by octaves
+    #[serde(default, skip_serializing_if = "is_strict_pairing")]
+    pub pairing: NotePairing,  // how Ons are matched with their Offs when rendering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization_gain: Option<f32>,  // frozen output gain, reused instead of normalizing each render
 }
 
-// Save song to a JSON file
-pub fn save_to_json(song: &Song, filename: &str) {
-    let json = serde_json::to_string(song).unwrap();
-    let mut file = File::create(filename).unwrap();
-    file.write_all(json.as_bytes()).unwrap();
+impl Song {
+    pub fn new(songname: &str, artist: &str, bpm: f32) -> Self {
+        Song {
+            songname: songname.to_string(),
+            artist: artist.to_string(),
+            bpm,
+            tempo_events: Vec::new(),
+            packets: Vec::new(),
+            tracks: Vec::new(),
+            lfos: Vec::new(),
+            modulations: Vec::new(),
+            variations: Vec::new(),
+            dynamics: Vec::new(),
+            grooves: Vec::new(),
+            mono: Vec::new(),
+            markers: Vec::new(),
+            morphs: Vec::new(),
+            drifts: Vec::new(),
+            releases: Vec::new(),
+            envelopes: Vec::new(),
+            effects: Vec::new(),
+            repeat: default_repeat(),
+            seed: 0,
+            fold_octaves: false,
+            pairing: NotePairing::Strict,
+            normalization_gain: None,
+        }
+    }
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+fn is_single_pass(repeat: &u32) -> bool {
+    *repeat == 1
+}
+
+fn is_default_seed(seed: &u64) -> bool {
+    *seed == 0
+}
+
+fn is_strict_pairing(pairing: &NotePairing) -> bool {
+    *pairing == NotePairing::Strict
+}
+
+// Save song to a JSON file, writing pitches as MIDI numbers or note names
+pub fn save_to_json(song: &Song, filename: &str, pitch_format: PitchFormat) -> io::Result<()> {
+    let json = with_pitch_format(pitch_format, || serde_json::to_string_pretty(song))?;
+    File::create(filename)?.write_all(json.as_bytes())
+}
+
+// Store a frozen normalization gain in a JSON song file. Only that field changes, the rest
+// of the file keeps its note names, named durations and field order.
+pub fn save_normalization_gain(filename: &str, gain: f32) -> io::Result<()> {
+    let mut json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(filename)?)?;
+    let fields = json.as_object_mut().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the song is not a JSON object"))?;
+    // Through the shortest text of the f32, so the file doesn't get the f64 widening's digits
+    let gain: f64 = gain.to_string().parse().unwrap_or(gain as f64);
+    fields.insert("normalization_gain".to_string(), gain.into());
+    std::fs::write(filename, serde_json::to_string_pretty(&json)?)
 }
 
 // Load song from a JSON file
@@ -23,5 +132,10 @@ pub fn load_from_json(filename: &str) -> Song {
     let mut file = File::open(filename).unwrap();
     let mut json = String::new();
     file.read_to_string(&mut json).unwrap();
-    serde_json::from_str(&json).unwrap()
+    load_from_str(&json).unwrap()
+}
+
+// Parse a song from JSON text, reporting malformed input instead of panicking
+pub fn load_from_str(json: &str) -> Result<Song, serde_json::Error> {
+    serde_json::from_str(json)
 }
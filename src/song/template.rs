@@ -0,0 +1,79 @@
+use super::instrument::Instrument;
+use super::note::{Note, packets_from_notes};
+use super::song::Song;
+use super::beats::Beats;
+
+pub const TEMPLATES: [&str; 3] = ["blank", "piano-chords", "pop"];
+
+// I-V-vi-IV in C major, one chord per bar
+const PROGRESSION: [([u8; 3], u8); 4] = [
+    ([60, 64, 67], 36),  // C
+    ([59, 62, 67], 43),  // G
+    ([60, 64, 69], 45),  // Am
+    ([60, 65, 69], 41),  // F
+];
+
+// A starter song so new projects don't begin with an empty packet list
+pub fn song_from_template(template: &str, songname: &str) -> Option<Song> {
+    let (bpm, notes) = match template {
+        "blank" => (120.0, vec![note(0.0, 1.0, 60, Instrument::Piano, 0.8)]),
+        "piano-chords" => (90.0, chords(Instrument::Piano, 4.0)),
+        "pop" => {
+            let mut notes = chords(Instrument::Piano, 4.0);
+            notes.extend(bassline());
+            notes.extend(melody());
+            (110.0, notes)
+        }
+        _ => return None,
+    };
+
+    let mut song = Song::new(songname, "Unknown", bpm);
+    song.packets = packets_from_notes(&notes);
+    Some(song)
+}
+
+fn note(start: f32, duration: f32, pitch: u8, instrument: Instrument, velocity: f32) -> Note {
+    Note { start: Beats::from_f32(start), duration: Beats::from_f32(duration), pitch, instrument, velocity, articulations: Vec::new(), envelope: None, pan: 0.0 }
+}
+
+fn chords(instrument: Instrument, beats_per_chord: f32) -> Vec<Note> {
+    PROGRESSION
+        .iter()
+        .enumerate()
+        .flat_map(|(bar, (chord, _))| {
+            let start = bar as f32 * beats_per_chord;
+            let instrument = instrument.clone();
+            chord.iter().map(move |&pitch| note(start, beats_per_chord, pitch, instrument.clone(), 0.5))
+        })
+        .collect()
+}
+
+// Root notes in eighths, accenting the downbeats
+fn bassline() -> Vec<Note> {
+    PROGRESSION
+        .iter()
+        .enumerate()
+        .flat_map(|(bar, (_, root))| {
+            (0..8).map(move |eighth| {
+                let velocity = if eighth % 2 == 0 { 0.6 } else { 0.4 };
+                note(bar as f32 * 4.0 + eighth as f32 * 0.5, 0.375, *root, Instrument::Saw, velocity)
+            })
+        })
+        .collect()
+}
+
+// A short motif over each chord, rising through the chord tones an octave up
+fn melody() -> Vec<Note> {
+    PROGRESSION
+        .iter()
+        .enumerate()
+        .flat_map(|(bar, (chord, _))| {
+            let rhythm = [(0.0, 1.0), (1.0, 0.5), (1.5, 0.5), (2.0, 2.0)];
+            let pitches = [chord[0] + 12, chord[1] + 12, chord[2] + 12, chord[1] + 12];
+            rhythm
+                .into_iter()
+                .zip(pitches)
+                .map(move |((offset, duration), pitch)| note(bar as f32 * 4.0 + offset, duration, pitch, Instrument::Square, 0.3))
+        })
+        .collect()
+}
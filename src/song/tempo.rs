@@ -0,0 +1,87 @@
+use serde::{Serialize, Deserialize};
+use super::beats::Beats;
+use super::song::Song;
+
+// Longest song that is rendered, a render holds all of it in memory. Songs that play
+// longer, e.g. because their tempo is tiny, render as silence.
+pub const MAX_SONG_SECONDS: f64 = 3600.0;
+
+// A new tempo from a beat on, e.g. for a faster chorus or a ritardando in steps.
+// Beats count from the start of the song as it plays, repeats included.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TempoChange {
+    pub beat: Beats,
+    pub bpm: f32,
+}
+
+// Where a tempo starts, in beats and in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TempoSegment {
+    beat: Beats,
+    seconds: f64,
+    bpm: f32,
+}
+
+// Converts between beats and seconds in a song whose tempo changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    segments: Vec<TempoSegment>,
+}
+
+impl TempoMap {
+    // Changes to a tempo that can't be played are ignored. A change at or before beat 0
+    // replaces the starting tempo, of two changes at the same beat the later one wins.
+    pub fn new(bpm: f32, changes: &[TempoChange]) -> Self {
+        let mut changes: Vec<&TempoChange> = changes.iter().filter(|change| change.bpm.is_finite() && change.bpm > 0.0).collect();
+        changes.sort_by_key(|change| change.beat);
+
+        let mut segments = vec![TempoSegment { beat: Beats::ZERO, seconds: 0.0, bpm }];
+        for change in changes {
+            let last = segments.last_mut().unwrap();
+            if change.beat <= last.beat {
+                last.bpm = change.bpm;
+                continue;
+            }
+            let seconds = last.seconds + (change.beat - last.beat).to_f64() * 60.0 / last.bpm as f64;
+            segments.push(TempoSegment { beat: change.beat, seconds, bpm: change.bpm });
+        }
+        TempoMap { segments }
+    }
+
+    pub fn constant(bpm: f32) -> Self {
+        TempoMap::new(bpm, &[])
+    }
+
+    // The tempo the song starts at
+    pub fn bpm(&self) -> f32 {
+        self.segments[0].bpm
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.segments.len() > 1
+    }
+
+    // Seconds from the start of the song to a beat. Beats before the start are timed at
+    // the starting tempo.
+    pub fn seconds_at(&self, beats: Beats) -> f64 {
+        let segment = self.segment_at_beat(beats);
+        segment.seconds + (beats - segment.beat).to_f64() * 60.0 / segment.bpm as f64
+    }
+
+    pub fn beats_at(&self, seconds: f64) -> f64 {
+        let index = self.segments.partition_point(|segment| segment.seconds <= seconds).saturating_sub(1);
+        let segment = &self.segments[index];
+        segment.beat.to_f64() + (seconds - segment.seconds) * segment.bpm as f64 / 60.0
+    }
+
+    fn segment_at_beat(&self, beats: Beats) -> &TempoSegment {
+        let index = self.segments.partition_point(|segment| segment.beat <= beats).saturating_sub(1);
+        &self.segments[index]
+    }
+}
+
+impl Song {
+    pub fn tempo_map(&self) -> TempoMap {
+        TempoMap::new(self.bpm, &self.tempo_events)
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Serialize, Deserialize};
+use super::instrument::Instrument;
+use super::midi_packet::MidiPacket;
+use super::beats::Beats;
+use super::song::Song;
+
+// A part of the song with its own packets, like a bassline or a melody, played at the
+// same time as the song's packets and the other tracks. Packets in a track file may
+// leave out the instrument, they then play the track's.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "TrackFile")]
+pub struct Track {
+    pub name: String,
+    pub instrument: Instrument,  // for packets that don't name one
+    #[serde(default = "default_volume")]
+    pub volume: f32,             // gain applied to the track's velocities
+    pub packets: Vec<MidiPacket>,
+}
+
+impl Track {
+    pub fn new(name: &str, instrument: Instrument) -> Self {
+        Track { name: name.to_string(), instrument, volume: default_volume(), packets: Vec::new() }
+    }
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+// A track as written in a song file, before the track's instrument is filled in
+#[derive(Deserialize)]
+struct TrackFile {
+    name: String,
+    instrument: Instrument,
+    #[serde(default = "default_volume")]
+    volume: f32,
+    packets: Vec<serde_json::Value>,
+}
+
+impl TryFrom<TrackFile> for Track {
+    type Error = serde_json::Error;
+
+    fn try_from(file: TrackFile) -> Result<Self, Self::Error> {
+        let instrument = serde_json::to_value(&file.instrument)?;
+        let packets = file
+            .packets
+            .into_iter()
+            .map(|mut packet| {
+                if let Some(fields) = packet.as_object_mut() {
+                    fields.entry("instrument").or_insert_with(|| instrument.clone());
+                }
+                serde_json::from_value(packet)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Track { name: file.name, instrument: file.instrument, volume: file.volume, packets })
+    }
+}
+
+impl Song {
+    // The song's packets and those of all tracks as one list, ordered by when they play.
+    // Track velocities are scaled by the track's volume; packets at the same beat keep
+    // the song's packets first, then the tracks in order.
+    pub fn mixed_packets(&self) -> Vec<MidiPacket> {
+        if self.tracks.is_empty() {
+            return self.packets.clone();
+        }
+
+        let mut timed: Vec<(Beats, MidiPacket)> = Vec::new();
+        let parts = std::iter::once((&self.packets, 1.0)).chain(self.tracks.iter().map(|track| (&track.packets, track.volume)));
+        for (packets, volume) in parts {
+            let mut position = Beats::ZERO;
+            for packet in packets {
+                position += packet.note_delta;
+                timed.push((position, MidiPacket { velocity: packet.velocity * volume, ..packet.clone() }));
+            }
+        }
+        // Stable, so every part keeps its own order
+        timed.sort_by_key(|(position, _)| *position);
+
+        let mut previous = Beats::ZERO;
+        timed
+            .into_iter()
+            .map(|(position, packet)| {
+                let note_delta = position - previous;
+                previous = position;
+                MidiPacket { note_delta, ..packet }
+            })
+            .collect()
+    }
+}
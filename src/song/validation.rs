@@ -0,0 +1,121 @@
+use super::beats::Beats;
+use super::note_status::NoteStatus;
+use super::midi_packet::MidiPacket;
+use super::song::Song;
+use super::modulation::LfoRate;
+use super::effect::{EffectSettings, MAX_DELAY_TIME};
+use super::groove::load_groove;
+use super::arrangement::flatten_packets;
+use super::tempo::MAX_SONG_SECONDS;
+use crate::units::note_name;
+use std::path::Path;
+
+// Problems that make a song render as silence or not at all
+pub fn song_warnings(song: &Song) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    // The tempo the song starts at, which a tempo change at beat 0 replaces
+    let tempo = song.tempo_map();
+    if !tempo.bpm().is_finite() || tempo.bpm() <= 0.0 {
+        warnings.push(format!("bpm is {}, the song will render as silence", tempo.bpm()));
+    }
+    for change in song.tempo_events.iter().filter(|change| !change.bpm.is_finite() || change.bpm <= 0.0) {
+        warnings.push(format!("tempo change to {} bpm at beat {} is ignored", change.bpm, change.beat));
+    }
+
+    let packets = song.mixed_packets();
+    if packets.is_empty() {
+        warnings.push("song has no packets".to_string());
+    } else if packets.iter().all(|packet| packet.note_status == NoteStatus::Off) {
+        warnings.push("song has no note-on packets".to_string());
+    }
+
+    let duration: Beats = packets.iter().map(|packet| packet.note_delta).sum();
+    if !packets.is_empty() && duration <= Beats::ZERO {
+        warnings.push("song has zero duration".to_string());
+    }
+
+    // Timed the way the render times it, repeats included
+    if tempo.bpm().is_finite() && tempo.bpm() > 0.0 {
+        let length: Beats = flatten_packets(song).iter().map(|packet| packet.note_delta).sum();
+        let seconds = tempo.seconds_at(length);
+        if seconds > MAX_SONG_SECONDS {
+            warnings.push(format!("song is {:.0} s long, longer than the {} s a render can be, it will render as silence", seconds, MAX_SONG_SECONDS));
+        }
+    }
+
+    for lfo in song.lfos.iter().filter(|lfo| song.modulations.iter().any(|route| route.lfo == lfo.name)) {
+        let (rate_hz, rate) = match lfo.rate {
+            LfoRate::Hz(hz) => (hz, format!("{} Hz", hz)),
+            LfoRate::Beats(beats) => (tempo.bpm() / 60.0 / beats, format!("{} beats", beats)),
+        };
+        if !rate_hz.is_finite() {
+            warnings.push(format!("LFO {} has a rate of {}, its modulation is ignored", lfo.name, rate));
+        }
+    }
+
+    for assignment in &song.grooves {
+        if let Err(error) = load_groove(&assignment.groove) {
+            warnings.push(format!("groove {} can't be loaded ({}), it will be skipped", assignment.groove, error));
+        }
+    }
+
+    for release in &song.releases {
+        if let Some(sample) = release.sample.as_ref().filter(|sample| !Path::new(sample).is_file()) {
+            warnings.push(format!("release sample {} for {} not found, noise will play instead", sample, release.instrument));
+        }
+    }
+
+    for effect in &song.effects {
+        match effect {
+            EffectSettings::LowPass { cutoff } | EffectSettings::HighPass { cutoff } if !cutoff.is_finite() || *cutoff <= 0.0 => {
+                warnings.push(format!("{} effect has a cutoff of {} Hz, it will filter at 1 Hz", effect.name(), cutoff));
+            }
+            EffectSettings::Delay { time, feedback, mix } => {
+                if time.is_nan() || *time <= 0.0 {
+                    warnings.push(format!("Delay effect has a time of {} s, it won't echo", time));
+                } else if *time > MAX_DELAY_TIME {
+                    warnings.push(format!("Delay effect has a time of {} s, it will be shortened to {} s", time, MAX_DELAY_TIME));
+                }
+                if feedback.is_nan() || feedback.abs() >= 1.0 {
+                    warnings.push(format!("Delay effect has a feedback of {}, it will be lowered so the echoes die away", feedback));
+                }
+                if !mix.is_finite() {
+                    warnings.push(format!("Delay effect has a mix of {}, it won't echo", mix));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let off_pan = packets.iter().filter(|packet| packet.note_status == NoteStatus::On && !(-1.0..=1.0).contains(&packet.pan)).count();
+    if off_pan > 0 {
+        warnings.push(format!("{} notes have a pan outside -1 to 1, it will be clamped", off_pan));
+    }
+
+    // One warning per instrument, naming the first offending note
+    let mut out_of_range: Vec<(&MidiPacket, usize)> = Vec::new();
+    for packet in packets.iter().filter(|packet| packet.note_status == NoteStatus::On) {
+        if packet.instrument.range().contains(&packet.pitch) {
+            continue;
+        }
+        match out_of_range.iter_mut().find(|(first, _)| first.instrument == packet.instrument) {
+            Some((_, count)) => *count += 1,
+            None => out_of_range.push((packet, 1)),
+        }
+    }
+    for (first, count) in out_of_range {
+        let range = first.instrument.range();
+        warnings.push(format!(
+            "{} {} notes outside its range {}-{}, e.g. {}{}",
+            count,
+            first.instrument,
+            note_name(*range.start()),
+            note_name(*range.end()),
+            note_name(first.pitch),
+            if song.fold_octaves { ", they will be folded into range" } else { "" },
+        ));
+    }
+
+    warnings
+}
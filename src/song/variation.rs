@@ -0,0 +1,27 @@
+use serde::{Serialize, Deserialize};
+use super::instrument::Instrument;
+
+// Randomized differences between triggers of the same instrument, so repeated
+// notes don't sound like identical copies. The seed makes renders reproducible.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Variation {
+    pub instrument: Instrument,
+    #[serde(default)]
+    pub detune_cents: f32,  // maximum detune, up or down
+    #[serde(default)]
+    pub velocity: f32,      // maximum relative velocity change, e.g. 0.1 for +-10%
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub phase: PhaseMode,
+}
+
+// Where each note's oscillator starts its cycle. Starting every note at phase 0
+// makes notes that start together interfere and click on the attack.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum PhaseMode {
+    #[default]
+    Fixed,   // always at 0
+    Random,  // anywhere, from the variation's seed
+    Spread,  // notes starting together are spread evenly over the cycle
+}
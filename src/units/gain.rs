@@ -0,0 +1,31 @@
+pub fn db_to_linear(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+// Silence maps to negative infinity
+pub fn linear_to_db(gain: f32) -> f32 {
+    20.0 * gain.abs().log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn db_and_linear_round_trip() {
+        assert_eq!(db_to_linear(0.0), 1.0);
+        assert_eq!(linear_to_db(1.0), 0.0);
+        assert!((db_to_linear(-6.0) - 0.501187).abs() < 1e-5);
+        assert!((linear_to_db(0.5) + 6.0206).abs() < 1e-3);
+        for db in [-96.0, -20.0, -3.0, 0.0, 6.0, 12.0] {
+            assert!((linear_to_db(db_to_linear(db)) - db).abs() < 1e-3, "{}", db);
+        }
+    }
+
+    #[test]
+    fn silence_is_negative_infinity() {
+        assert_eq!(linear_to_db(0.0), f32::NEG_INFINITY);
+        assert_eq!(db_to_linear(f32::NEG_INFINITY), 0.0);
+        assert_eq!(linear_to_db(-0.5), linear_to_db(0.5));
+    }
+}
@@ -0,0 +1,7 @@
+mod time;
+mod pitch;
+mod gain;
+
+pub use time::{seconds_per_beat, beats_to_seconds, seconds_to_beats, seconds_to_samples, samples_to_seconds, beats_to_samples, samples_to_beats};
+pub use pitch::{midi_to_frequency, frequency_to_midi, note_name, pitch_class_name, parse_note_name};
+pub use gain::{db_to_linear, linear_to_db};
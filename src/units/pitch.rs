@@ -0,0 +1,99 @@
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+// Equal temperament with A4 (MIDI 69) at 440 Hz; fractional pitches are detuned notes
+pub fn midi_to_frequency(pitch: f32) -> f32 {
+    440.0 * 2.0f32.powf((pitch - 69.0) / 12.0)
+}
+
+pub fn frequency_to_midi(frequency: f32) -> f32 {
+    69.0 + 12.0 * (frequency / 440.0).log2()
+}
+
+// Scientific pitch notation with sharps, where MIDI 60 is "C4"
+pub fn note_name(pitch: u8) -> String {
+    let octave = pitch as i32 / 12 - 1;
+    format!("{}{}", NOTE_NAMES[pitch as usize % 12], octave)
+}
+
+// Name of a pitch class with sharps, where 0 is "C"
+pub fn pitch_class_name(pitch_class: u8) -> &'static str {
+    NOTE_NAMES[pitch_class as usize % 12]
+}
+
+// Parse names like "C4", "c#4", "Db3", "F##2" or "B-1" into a MIDI pitch
+pub fn parse_note_name(name: &str) -> Option<u8> {
+    let mut chars = name.trim().chars().peekable();
+
+    let mut semitone: i32 = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    while let Some(&accidental) = chars.peek() {
+        match accidental {
+            '#' | '♯' => semitone += 1,
+            'b' | '♭' => semitone -= 1,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    let octave: i32 = chars.collect::<String>().parse().ok()?;
+    let pitch = (octave + 1) * 12 + semitone;
+    u8::try_from(pitch).ok().filter(|pitch| *pitch <= 127)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a4_is_440_hz() {
+        assert_eq!(midi_to_frequency(69.0), 440.0);
+        assert_eq!(frequency_to_midi(440.0), 69.0);
+        assert!((midi_to_frequency(60.0) - 261.6256).abs() < 1e-3);
+    }
+
+    #[test]
+    fn frequency_to_midi_inverts_midi_to_frequency() {
+        for pitch in (0..=127).map(|pitch| pitch as f32).chain([60.5, 69.25]) {
+            assert!((frequency_to_midi(midi_to_frequency(pitch)) - pitch).abs() < 1e-3, "{}", pitch);
+        }
+    }
+
+    #[test]
+    fn note_names_round_trip() {
+        for pitch in 0..=127u8 {
+            assert_eq!(parse_note_name(&note_name(pitch)), Some(pitch));
+        }
+        assert_eq!(note_name(60), "C4");
+        assert_eq!(note_name(0), "C-1");
+        assert_eq!(note_name(127), "G9");
+    }
+
+    #[test]
+    fn parses_accidentals_and_octave_edges() {
+        assert_eq!(parse_note_name("C-1"), Some(0));
+        assert_eq!(parse_note_name("B#3"), Some(60));
+        assert_eq!(parse_note_name("Cb4"), Some(59));
+        assert_eq!(parse_note_name("c#4"), Some(61));
+        assert_eq!(parse_note_name("F##2"), Some(43));
+        assert_eq!(parse_note_name("D♭3"), Some(49));
+    }
+
+    #[test]
+    fn rejects_pitches_outside_midi() {
+        assert_eq!(parse_note_name("G#9"), None);
+        assert_eq!(parse_note_name("C10"), None);
+        assert_eq!(parse_note_name("Cb-1"), None);
+        assert_eq!(parse_note_name("H4"), None);
+        assert_eq!(parse_note_name("C"), None);
+        assert_eq!(parse_note_name(""), None);
+    }
+}
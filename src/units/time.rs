@@ -0,0 +1,65 @@
+pub fn seconds_per_beat(bpm: f32) -> f32 {
+    60.0 / bpm
+}
+
+pub fn beats_to_seconds(beats: f32, bpm: f32) -> f32 {
+    beats * seconds_per_beat(bpm)
+}
+
+pub fn seconds_to_beats(seconds: f32, bpm: f32) -> f32 {
+    seconds / seconds_per_beat(bpm)
+}
+
+// Sample positions are truncated, matching how the renderer places notes
+pub fn seconds_to_samples(seconds: f32, sample_rate: u32) -> usize {
+    (seconds * sample_rate as f32) as usize
+}
+
+pub fn samples_to_seconds(samples: usize, sample_rate: u32) -> f32 {
+    samples as f32 / sample_rate as f32
+}
+
+pub fn beats_to_samples(beats: f32, bpm: f32, sample_rate: u32) -> usize {
+    seconds_to_samples(beats_to_seconds(beats, bpm), sample_rate)
+}
+
+pub fn samples_to_beats(samples: usize, bpm: f32, sample_rate: u32) -> f32 {
+    seconds_to_beats(samples_to_seconds(samples, sample_rate), bpm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beats_and_seconds_round_trip() {
+        for bpm in [60.0, 90.0, 120.0, 137.5] {
+            for beats in [0.0, 0.25, 1.0, 3.5, 64.0] {
+                let seconds = beats_to_seconds(beats, bpm);
+                assert!((seconds_to_beats(seconds, bpm) - beats).abs() < 1e-4);
+            }
+        }
+        assert_eq!(beats_to_seconds(2.0, 120.0), 1.0);
+    }
+
+    #[test]
+    fn seconds_and_samples_round_trip() {
+        for sample_rate in [22050, 44100, 48000, 96000] {
+            for samples in [0, 1, 441, 44100, 1_000_000] {
+                let seconds = samples_to_seconds(samples, sample_rate);
+                assert_eq!(seconds_to_samples(seconds, sample_rate), samples);
+            }
+        }
+        assert_eq!(seconds_to_samples(0.5, 44100), 22050);
+    }
+
+    #[test]
+    fn beats_and_samples_round_trip() {
+        assert_eq!(beats_to_samples(1.0, 120.0, 44100), 22050);
+        assert_eq!(samples_to_beats(22050, 120.0, 44100), 1.0);
+        for beats in [0.5, 1.0, 4.0, 16.0] {
+            let samples = beats_to_samples(beats, 120.0, 48000);
+            assert_eq!(samples_to_beats(samples, 120.0, 48000), beats);
+        }
+    }
+}
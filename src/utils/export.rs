@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, Write};
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+use super::npy::{write_npy, write_npz};
+use super::utils::write_csv;
+use super::wav::BitDepth;
+use super::wav_tags::{WavTags, write_wav_with_tags};
+
+// What an exporter may store alongside the samples; formats without metadata ignore it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportMeta {
+    pub sample_rate: u32,
+    pub channels: u16,        // the buffer holds interleaved frames of this many samples
+    pub tags: WavTags,
+    pub bit_depth: BitDepth,  // for formats that can store more than one
+}
+
+impl ExportMeta {
+    // Mono, set `channels` for a render
+    pub fn new(sample_rate: u32) -> Self {
+        ExportMeta { sample_rate, channels: 1, tags: WavTags::default(), bit_depth: BitDepth::default() }
+    }
+}
+
+// What exporters write to, positioned at the start of the file. Seekable so formats can
+// fill in sizes in their header once the data is written, without buffering the file.
+pub trait ExportWriter: Write + Seek {}
+
+impl<W: Write + Seek> ExportWriter for W {}
+
+// Writes a waveform in one file format
+pub trait Exporter: Send + Sync {
+    fn export(&self, buffer: &[f32], meta: &ExportMeta, writer: &mut dyn ExportWriter) -> io::Result<()>;
+}
+
+// 16-bit or 32-bit float WAV with the tags as INFO list and ID3 chunk
+pub struct WavExporter;
+
+impl Exporter for WavExporter {
+    fn export(&self, buffer: &[f32], meta: &ExportMeta, writer: &mut dyn ExportWriter) -> io::Result<()> {
+        write_wav_with_tags(buffer, meta.channels, meta.sample_rate, meta.bit_depth, &meta.tags, writer).map_err(|error| match error {
+            hound::Error::IoError(error) => error,
+            error => io::Error::other(error),
+        })
+    }
+}
+
+// One frame per line, channels separated by commas
+pub struct CsvExporter;
+
+impl Exporter for CsvExporter {
+    fn export(&self, buffer: &[f32], meta: &ExportMeta, writer: &mut dyn ExportWriter) -> io::Result<()> {
+        write_csv(buffer.iter().copied(), meta.channels, writer)
+    }
+}
+
+pub struct NpyExporter;
+
+impl Exporter for NpyExporter {
+    fn export(&self, buffer: &[f32], meta: &ExportMeta, writer: &mut dyn ExportWriter) -> io::Result<()> {
+        write_npy(buffer, meta.channels, writer)
+    }
+}
+
+// Waveform, sample rate and duration as a NumPy archive
+pub struct NpzExporter;
+
+impl Exporter for NpzExporter {
+    fn export(&self, buffer: &[f32], meta: &ExportMeta, writer: &mut dyn ExportWriter) -> io::Result<()> {
+        write_npz(buffer, meta.channels, meta.sample_rate, writer)
+    }
+}
+
+static EXPORTERS: OnceLock<RwLock<BTreeMap<String, Arc<dyn Exporter>>>> = OnceLock::new();
+
+fn exporters() -> &'static RwLock<BTreeMap<String, Arc<dyn Exporter>>> {
+    EXPORTERS.get_or_init(|| {
+        let built_in: [(&str, Arc<dyn Exporter>); 4] =
+            [("wav", Arc::new(WavExporter)), ("csv", Arc::new(CsvExporter)), ("npy", Arc::new(NpyExporter)), ("npz", Arc::new(NpzExporter))];
+        RwLock::new(built_in.into_iter().map(|(extension, exporter)| (extension.to_string(), exporter)).collect())
+    })
+}
+
+// Make a format available for files ending in `.extension` (without the dot, any case)
+// Registering an existing extension replaces its exporter, built-in ones included
+pub fn register_exporter(extension: &str, exporter: Arc<dyn Exporter>) {
+    exporters().write().unwrap().insert(extension.to_lowercase(), exporter);
+}
+
+pub fn exporter_for(extension: &str) -> Option<Arc<dyn Exporter>> {
+    exporters().read().unwrap().get(&extension.to_lowercase()).cloned()
+}
+
+pub fn exporter_extensions() -> Vec<String> {
+    exporters().read().unwrap().keys().cloned().collect()
+}
+
+// Save a waveform with the exporter registered for the file's extension
+pub fn export_to_file(buffer: &[f32], meta: &ExportMeta, filename: &str) -> io::Result<()> {
+    let extension = Path::new(filename).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    let exporter = exporter_for(extension)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, format!("no exporter for .{} files", extension)))?;
+
+    let mut writer = BufWriter::new(File::create(filename)?);
+    exporter.export(buffer, meta, &mut writer)?;
+    writer.flush()
+}
@@ -1,3 +1,16 @@
+#[allow(clippy::module_inception)]
 mod utils;
+mod random;
+mod wav;
+mod wav_tags;
+mod npy;
+mod pcm;
+mod export;
 
-pub use utils::save_vec_to_csv;
+pub use utils::{save_vec_to_csv, write_csv};
+pub use npy::{save_vec_to_npy, save_vec_to_npz, write_npy, write_npz};
+pub use wav::{BitDepth, save_wav, save_wav_as, write_wav, load_wav, read_wav};
+pub use wav_tags::{WavTags, save_wav_with_tags, write_wav_with_tags, read_wav_tags, read_wav_tags_from};
+pub use pcm::{to_s16le, streaming_wav_header};
+pub use random::{random_unit, random_bipolar};
+pub use export::{Exporter, ExportWriter, ExportMeta, WavExporter, CsvExporter, NpyExporter, NpzExporter, register_exporter, exporter_for, exporter_extensions, export_to_file};
@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Save a waveform as a NumPy .npy array of little-endian float32
+pub fn save_vec_to_npy(data: &[f32], filename: &str) -> std::io::Result<()> {
+    write_npy(data, 1, BufWriter::new(File::create(filename)?))
+}
+
+// Interleaved frames of more than one channel are stored as an array of shape
+// (frames, channels)
+pub fn write_npy<W: Write>(data: &[f32], channels: u16, mut writer: W) -> std::io::Result<()> {
+    writer.write_all(&waveform_array(data, channels))?;
+    writer.flush()
+}
+
+// Save a waveform with its metadata as a NumPy .npz archive, loadable with
+// numpy.load(): arrays "waveform", "sample_rate" and "duration" (seconds)
+pub fn save_vec_to_npz(data: &[f32], sample_rate: u32, filename: &str) -> std::io::Result<()> {
+    write_npz(data, 1, sample_rate, BufWriter::new(File::create(filename)?))
+}
+
+pub fn write_npz<W: Write>(data: &[f32], channels: u16, sample_rate: u32, mut writer: W) -> std::io::Result<()> {
+    let duration = (data.len() / channels.max(1) as usize) as f32 / sample_rate as f32;
+    let entries = [
+        ("waveform.npy", waveform_array(data, channels)),
+        ("sample_rate.npy", npy_bytes("<u4", "()", &sample_rate.to_le_bytes())),
+        ("duration.npy", npy_bytes("<f4", "()", &duration.to_le_bytes())),
+    ];
+
+    write_stored_zip(&mut writer, &entries)?;
+    writer.flush()
+}
+
+// A trailing partial frame is left out
+fn waveform_array(data: &[f32], channels: u16) -> Vec<u8> {
+    match channels {
+        0 | 1 => npy_bytes("<f4", &format!("({},)", data.len()), &f32_bytes(data)),
+        _ => {
+            let frames = data.len() / channels as usize;
+            npy_bytes("<f4", &format!("({}, {})", frames, channels), &f32_bytes(&data[..frames * channels as usize]))
+        }
+    }
+}
+
+fn f32_bytes(data: &[f32]) -> Vec<u8> {
+    data.iter().flat_map(|sample| sample.to_le_bytes()).collect()
+}
+
+// NPY format version 1.0: magic, header length, a Python dict literal padded so the
+// data starts on a 64-byte boundary, then the raw array data
+fn npy_bytes(descr: &str, shape: &str, data: &[u8]) -> Vec<u8> {
+    let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", descr, shape);
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(10 + header.len() + data.len());
+    bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+// Minimal uncompressed zip archive, which is all numpy needs for .npz files
+fn write_stored_zip<W: Write>(writer: &mut W, entries: &[(&str, Vec<u8>)]) -> std::io::Result<()> {
+    const DOS_DATE_1980_01_01: u16 = (1 << 5) | 1;
+
+    let mut central_directory = Vec::new();
+    let mut offset = 0u32;
+
+    for (name, data) in entries {
+        let crc = crc32(data);
+        let size = data.len() as u32;
+
+        // Fields shared by the local header and the central directory entry
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&20u16.to_le_bytes());  // version needed to extract
+        fields.extend_from_slice(&0u16.to_le_bytes());   // flags
+        fields.extend_from_slice(&0u16.to_le_bytes());   // compression: stored
+        fields.extend_from_slice(&0u16.to_le_bytes());   // modification time
+        fields.extend_from_slice(&DOS_DATE_1980_01_01.to_le_bytes());
+        fields.extend_from_slice(&crc.to_le_bytes());
+        fields.extend_from_slice(&size.to_le_bytes());   // compressed size
+        fields.extend_from_slice(&size.to_le_bytes());   // uncompressed size
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&0u16.to_le_bytes());   // extra field length
+
+        writer.write_all(&0x0403_4b50u32.to_le_bytes())?;
+        writer.write_all(&fields)?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(data)?;
+
+        central_directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central_directory.extend_from_slice(&20u16.to_le_bytes());  // version made by
+        central_directory.extend_from_slice(&fields);
+        central_directory.extend_from_slice(&0u16.to_le_bytes());   // comment length
+        central_directory.extend_from_slice(&0u16.to_le_bytes());   // disk number
+        central_directory.extend_from_slice(&0u16.to_le_bytes());   // internal attributes
+        central_directory.extend_from_slice(&0u32.to_le_bytes());   // external attributes
+        central_directory.extend_from_slice(&offset.to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+
+        offset += 30 + name.len() as u32 + size;
+    }
+
+    writer.write_all(&central_directory)?;
+
+    // End of central directory record
+    writer.write_all(&0x0605_4b50u32.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+    writer.write_all(&(entries.len() as u16).to_le_bytes())?;
+    writer.write_all(&(entries.len() as u16).to_le_bytes())?;
+    writer.write_all(&(central_directory.len() as u32).to_le_bytes())?;
+    writer.write_all(&offset.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
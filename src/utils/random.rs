@@ -0,0 +1,17 @@
+// Deterministic pseudo-random value in [0, 1) for a (seed, index) pair (splitmix64),
+// so randomized rendering is reproducible from the song file alone
+pub fn random_unit(seed: u64, index: u64) -> f32 {
+    let mut z = seed
+        .wrapping_mul(0xD1B5_4A32_D192_ED03)
+        .wrapping_add(index)
+        .wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+// Same as random_unit, scaled to [-1, 1)
+pub fn random_bipolar(seed: u64, index: u64) -> f32 {
+    random_unit(seed, index) * 2.0 - 1.0
+}
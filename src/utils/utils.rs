@@ -1,10 +1,21 @@
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 
-pub fn save_vec_to_csv(data: Vec<f32>, filename: &str) -> std::io::Result<()> {
-    let mut file = File::create(filename)?;
-    for value in data {
-        writeln!(file, "{}", value)?;
+pub fn save_vec_to_csv(data: &[f32], filename: &str) -> std::io::Result<()> {
+    let file = File::create(filename)?;
+    write_csv(data.iter().copied(), 1, BufWriter::new(file))
+}
+
+// Write interleaved frames of `channels` samples one frame per line, the channels
+// separated by commas, to any writer without collecting them first
+pub fn write_csv<W: Write>(samples: impl IntoIterator<Item = f32>, channels: u16, mut writer: W) -> std::io::Result<()> {
+    let channels = channels.max(1) as usize;
+    for (index, value) in samples.into_iter().enumerate() {
+        if (index + 1) % channels == 0 {
+            writeln!(writer, "{}", value)?;
+        } else {
+            write!(writer, "{},", value)?;
+        }
     }
-    Ok(())
+    writer.flush()
 }
@@ -0,0 +1,81 @@
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+
+// How the samples of a WAV file are stored. Float keeps everything the renderer
+// produces, 16-bit is what every audio tool can open.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum BitDepth {
+    Int16,
+    #[default]
+    Float32,
+}
+
+impl BitDepth {
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        match bits {
+            16 => Some(BitDepth::Int16),
+            32 => Some(BitDepth::Float32),
+            _ => None,
+        }
+    }
+}
+
+// Save a mono waveform as a 32-bit float WAV file
+pub fn save_wav(data: &[f32], sample_rate: u32, filename: &str) -> Result<(), hound::Error> {
+    save_wav_as(data, 1, sample_rate, BitDepth::Float32, filename)
+}
+
+// Save interleaved frames of `channels` samples
+pub fn save_wav_as(data: &[f32], channels: u16, sample_rate: u32, bit_depth: BitDepth, filename: &str) -> Result<(), hound::Error> {
+    write_wav(data, channels, sample_rate, bit_depth, BufWriter::new(File::create(filename)?))
+}
+
+// 16-bit samples are clipped to full scale
+pub fn write_wav<W: Write + Seek>(data: &[f32], channels: u16, sample_rate: u32, bit_depth: BitDepth, writer: W) -> Result<(), hound::Error> {
+    let (bits_per_sample, sample_format) = match bit_depth {
+        BitDepth::Int16 => (16, SampleFormat::Int),
+        BitDepth::Float32 => (32, SampleFormat::Float),
+    };
+    let spec = WavSpec { channels, sample_rate, bits_per_sample, sample_format };
+
+    let mut writer = WavWriter::new(writer, spec)?;
+    for &sample in data {
+        match bit_depth {
+            BitDepth::Int16 => writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)?,
+            BitDepth::Float32 => writer.write_sample(sample)?,
+        }
+    }
+    writer.finalize()
+}
+
+// Load a WAV file as mono float samples (channels are averaged), with its sample rate
+pub fn load_wav(filename: &str) -> Result<(Vec<f32>, u32), hound::Error> {
+    read_wav(BufReader::new(File::open(filename)?))
+}
+
+// The same from any reader, e.g. WAV data already in memory
+pub fn read_wav<R: Read>(reader: R) -> Result<(Vec<f32>, u32), hound::Error> {
+    let mut reader = WavReader::new(reader)?;
+    let spec = reader.spec();
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            // Headers can claim any width, samples wider than 32 bits fail to read below
+            let scale = 1.0 / 2f32.powi(spec.bits_per_sample as i32 - 1);
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    let mono = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    Ok((mono, spec.sample_rate))
+}
@@ -0,0 +1,353 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use super::wav::{BitDepth, write_wav};
+
+// Text tags stored in a WAV file alongside the audio
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WavTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub bpm: Option<f32>,
+    pub comment: Option<String>,
+    pub software: Option<String>,         // what generated the file
+    pub replay_gain: Option<(f32, f32)>,  // ReplayGain track gain in dB and track peak
+}
+
+// Save a mono waveform as a 32-bit float WAV file with its tags, both as a RIFF INFO
+// list and as an ID3 chunk, since players read one or the other
+pub fn save_wav_with_tags(data: &[f32], sample_rate: u32, filename: &str, tags: &WavTags) -> Result<(), hound::Error> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    write_wav_with_tags(data, 1, sample_rate, BitDepth::Float32, tags, &mut writer)?;
+    Ok(writer.flush()?)
+}
+
+// The same for any writer that starts at the beginning of the file, with interleaved
+// frames of `channels` samples. The audio is written in place, then the tag chunks,
+// whose size is known up front, are appended and the RIFF size is patched.
+pub fn write_wav_with_tags<W: Write + Seek>(data: &[f32], channels: u16, sample_rate: u32, bit_depth: BitDepth, tags: &WavTags, mut writer: W) -> Result<(), hound::Error> {
+    let mut chunks = Vec::new();
+    let info = info_list(tags);
+    if info.len() > 4 {
+        chunks.extend(chunk(b"LIST", &info));
+    }
+    let frames = id3_frames(tags);
+    if !frames.is_empty() {
+        chunks.extend(chunk(b"id3 ", &id3_tag(&frames)));
+    }
+
+    write_wav(data, channels, sample_rate, bit_depth, &mut writer)?;
+    if !chunks.is_empty() {
+        let riff_size = writer.seek(SeekFrom::End(0))? - 8 + chunks.len() as u64;
+        writer.write_all(&chunks)?;
+        writer.seek(SeekFrom::Start(4))?;
+        writer.write_all(&(riff_size as u32).to_le_bytes())?;
+        writer.seek(SeekFrom::End(0))?;
+    }
+    Ok(())
+}
+
+// Read the tags of a WAV file from its INFO list and ID3 chunk; where both have a
+// value the ID3 one wins. Files without tags give empty tags.
+pub fn read_wav_tags(filename: &str) -> io::Result<WavTags> {
+    read_wav_tags_from(BufReader::new(File::open(filename)?))
+}
+
+// The same from any reader, e.g. WAV data already in memory
+pub fn read_wav_tags_from<R: Read + Seek>(mut file: R) -> io::Result<WavTags> {
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a WAV file"));
+    }
+
+    let mut tags = WavTags::default();
+    let mut info = WavTags::default();
+    let mut chunk_header = [0u8; 8];
+    while file.read_exact(&mut chunk_header).is_ok() {
+        let size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as u64;
+        match &chunk_header[0..4] {
+            b"LIST" | b"id3 " | b"ID3 " => {
+                // The size comes from the file, so only read what is really there
+                let mut data = Vec::new();
+                (&mut file).take(size).read_to_end(&mut data)?;
+                if (data.len() as u64) < size {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "tag chunk is truncated"));
+                }
+                if &chunk_header[0..4] == b"LIST" {
+                    read_info_list(&data, &mut info);
+                } else {
+                    read_id3_tag(&data, &mut tags);
+                }
+                file.seek(SeekFrom::Current((size % 2) as i64))?;
+            }
+            _ => {
+                file.seek(SeekFrom::Current((size + size % 2) as i64))?;
+            }
+        }
+    }
+
+    Ok(WavTags {
+        title: tags.title.or(info.title),
+        artist: tags.artist.or(info.artist),
+        bpm: tags.bpm,
+        comment: tags.comment.or(info.comment),
+        software: tags.software.or(info.software),
+        replay_gain: tags.replay_gain,
+    })
+}
+
+// RIFF INFO list: NUL-terminated strings, each padded to an even length
+fn info_list(tags: &WavTags) -> Vec<u8> {
+    let mut list = b"INFO".to_vec();
+    let fields = [(b"INAM", &tags.title), (b"IART", &tags.artist), (b"ICMT", &tags.comment), (b"ISFT", &tags.software)];
+    for (id, value) in fields {
+        if let Some(value) = value {
+            list.extend(id);
+            list.extend((value.len() as u32 + 1).to_le_bytes());
+            list.extend(value.as_bytes());
+            list.push(0);
+            if (value.len() + 1) % 2 == 1 {
+                list.push(0);
+            }
+        }
+    }
+    list
+}
+
+fn read_info_list(data: &[u8], tags: &mut WavTags) {
+    if data.get(0..4) != Some(b"INFO") {
+        return;
+    }
+
+    let mut position = 4;
+    while position + 8 <= data.len() {
+        let id = &data[position..position + 4];
+        let size = u32::from_le_bytes(data[position + 4..position + 8].try_into().unwrap()) as usize;
+        let Some(value) = data.get(position + 8..position + 8 + size) else {
+            return;
+        };
+        let value = String::from_utf8_lossy(value).trim_end_matches('\0').to_string();
+        match id {
+            b"INAM" => tags.title = Some(value),
+            b"IART" => tags.artist = Some(value),
+            b"ICMT" => tags.comment = Some(value),
+            b"ISFT" => tags.software = Some(value),
+            _ => {}
+        }
+        position += 8 + size + size % 2;
+    }
+}
+
+fn id3_frames(tags: &WavTags) -> Vec<u8> {
+    let mut frames = Vec::new();
+    if let Some(title) = &tags.title {
+        id3_text_frame(&mut frames, b"TIT2", title);
+    }
+    if let Some(artist) = &tags.artist {
+        id3_text_frame(&mut frames, b"TPE1", artist);
+    }
+    if let Some(bpm) = tags.bpm {
+        // Written exactly, players that want a whole number round it themselves
+        id3_text_frame(&mut frames, b"TBPM", &bpm.to_string());
+    }
+    if let Some(comment) = &tags.comment {
+        // Language, then an empty description
+        id3_text_frame(&mut frames, b"COMM", &format!("eng\0{}", comment));
+    }
+    if let Some(software) = &tags.software {
+        id3_text_frame(&mut frames, b"TSSE", software);
+    }
+    if let Some((gain, peak)) = tags.replay_gain {
+        id3_text_frame(&mut frames, b"TXXX", &format!("REPLAYGAIN_TRACK_GAIN\0{:.2} dB", gain));
+        id3_text_frame(&mut frames, b"TXXX", &format!("REPLAYGAIN_TRACK_PEAK\0{:.6}", peak));
+    }
+    frames
+}
+
+// ID3v2.4 tag around the given frames
+fn id3_tag(frames: &[u8]) -> Vec<u8> {
+    let mut tag = b"ID3\x04\x00\x00".to_vec();
+    tag.extend(syncsafe(frames.len() as u32));
+    tag.extend(frames);
+    tag
+}
+
+// A UTF-8 text frame; TXXX frames separate description and value with a NUL
+fn id3_text_frame(frames: &mut Vec<u8>, id: &[u8; 4], text: &str) {
+    frames.extend(id);
+    frames.extend(syncsafe(text.len() as u32 + 1));
+    frames.extend([0, 0, 3]);  // no flags, UTF-8
+    frames.extend(text.as_bytes());
+}
+
+// ID3v2.4 sizes use 7 bits per byte
+fn syncsafe(size: u32) -> [u8; 4] {
+    [(size >> 21) as u8 & 0x7f, (size >> 14) as u8 & 0x7f, (size >> 7) as u8 & 0x7f, size as u8 & 0x7f]
+}
+
+fn from_syncsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |size, byte| (size << 7) | (*byte & 0x7f) as usize)
+}
+
+// Reads ID3v2.3 and v2.4 text frames; anything else is skipped
+fn read_id3_tag(data: &[u8], tags: &mut WavTags) {
+    if data.len() < 10 || &data[0..3] != b"ID3" {
+        return;
+    }
+    let version = data[3];
+    let end = (10 + from_syncsafe(&data[6..10])).min(data.len());
+
+    let mut position = 10;
+    while position + 10 <= end && data[position] != 0 {
+        let id = &data[position..position + 4];
+        let size = if version >= 4 {
+            from_syncsafe(&data[position + 4..position + 8])
+        } else {
+            u32::from_be_bytes(data[position + 4..position + 8].try_into().unwrap()) as usize
+        };
+        let Some(body) = data.get(position + 10..position + 10 + size) else {
+            return;
+        };
+        let text = body.split_first().map(|(encoding, text)| decode_id3_text(*encoding, text)).unwrap_or_default();
+        let first = text.first().cloned();
+
+        match id {
+            b"TIT2" => tags.title = first,
+            b"TPE1" => tags.artist = first,
+            b"TBPM" => tags.bpm = first.and_then(|bpm| bpm.trim().parse().ok()),
+            b"TSSE" => tags.software = first,
+            b"COMM" => {
+                // The language code sits before the encoded description and text
+                let text = body.get(4..).map(|text| decode_id3_text(body[0], text)).unwrap_or_default();
+                tags.comment = text.get(1).cloned();
+            }
+            b"TXXX" => match (text.first().map(String::as_str), text.get(1)) {
+                (Some("REPLAYGAIN_TRACK_GAIN"), Some(gain)) => {
+                    let gain = gain.trim_end_matches("dB").trim().parse().ok();
+                    let peak = tags.replay_gain.map_or(1.0, |(_, peak)| peak);
+                    tags.replay_gain = gain.map(|gain| (gain, peak));
+                }
+                (Some("REPLAYGAIN_TRACK_PEAK"), Some(peak)) => {
+                    if let (Some((gain, _)), Ok(peak)) = (tags.replay_gain, peak.trim().parse()) {
+                        tags.replay_gain = Some((gain, peak));
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        position += 10 + size;
+    }
+}
+
+// Text in one of ID3's encodings, split at its NUL separators
+fn decode_id3_text(encoding: u8, text: &[u8]) -> Vec<String> {
+    let text = match encoding {
+        // Latin-1 maps straight onto the first Unicode code points
+        0 => text.iter().map(|&byte| byte as char).collect(),
+        1 | 2 => {
+            let mut units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|pair| if encoding == 2 { u16::from_be_bytes([pair[0], pair[1]]) } else { u16::from_le_bytes([pair[0], pair[1]]) })
+                .collect();
+            // A byte order mark says big endian if it reads backwards
+            if units.contains(&0xfffe) {
+                units = units.iter().map(|unit| unit.swap_bytes()).collect();
+            }
+            units.retain(|unit| *unit != 0xfeff);
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(text).into_owned(),
+    };
+
+    let text: &str = &text;
+    text.trim_end_matches('\0').split('\0').map(str::to_string).collect()
+}
+
+// Append a chunk to a complete RIFF file and update the RIFF size to include it
+fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+    chunk.extend((data.len() as u32).to_le_bytes());
+    chunk.extend(data);
+    // Chunks are padded to an even length
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::utils::read_wav;
+
+    fn tags() -> WavTags {
+        WavTags {
+            title: Some("Round Trip".to_string()),
+            artist: Some("Synthia".to_string()),
+            bpm: Some(123.5),
+            comment: Some("Ünïcode comment".to_string()),
+            software: Some("Synthia test".to_string()),
+            replay_gain: Some((-6.5, 0.75)),
+        }
+    }
+
+    fn write(tags: &WavTags) -> Vec<u8> {
+        let mut file = Cursor::new(Vec::new());
+        write_wav_with_tags(&[0.0, 0.5, -0.5, 0.25], 2, 44100, BitDepth::Float32, tags, &mut file).unwrap();
+        file.into_inner()
+    }
+
+    fn chunk_start(data: &[u8], id: &[u8; 4]) -> usize {
+        data.windows(4).position(|window| window == id).unwrap()
+    }
+
+    #[test]
+    fn round_trips_tags_and_audio() {
+        let data = write(&tags());
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize, data.len() - 8);
+        assert_eq!(read_wav_tags_from(Cursor::new(&data)).unwrap(), tags());
+        // Channels are averaged
+        assert_eq!(read_wav(Cursor::new(&data)).unwrap(), (vec![0.25, -0.125], 44100));
+    }
+
+    #[test]
+    fn files_without_tags_have_empty_tags() {
+        let data = write(&WavTags::default());
+        assert!(data.windows(4).all(|window| window != b"LIST" && window != b"id3 "));
+        assert_eq!(read_wav_tags_from(Cursor::new(&data)).unwrap(), WavTags::default());
+    }
+
+    #[test]
+    fn reads_the_info_list_without_an_id3_chunk() {
+        let data = write(&tags());
+        let info_only = &data[..chunk_start(&data, b"id3 ")];
+        let expected = WavTags { bpm: None, replay_gain: None, ..tags() };
+        assert_eq!(read_wav_tags_from(Cursor::new(info_only)).unwrap(), expected);
+    }
+
+    #[test]
+    fn truncated_tag_chunks_are_invalid_data() {
+        let data = write(&tags());
+        let id3 = chunk_start(&data, b"id3 ");
+        for length in id3 + 8..data.len() - 1 {
+            let error = read_wav_tags_from(Cursor::new(&data[..length])).err().unwrap_or_else(|| panic!("{} bytes read", length));
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{} bytes", length);
+        }
+    }
+
+    #[test]
+    fn oversized_tag_chunks_are_invalid_data() {
+        let mut data = write(&tags());
+        let id3 = chunk_start(&data, b"id3 ");
+        data[id3 + 4..id3 + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(read_wav_tags_from(Cursor::new(&data)).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn other_files_are_invalid_data() {
+        assert_eq!(read_wav_tags_from(Cursor::new(b"RIFF\0\0\0\0AVI ")).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(read_wav_tags_from(Cursor::new(b"RIFF")).is_err());
+    }
+}
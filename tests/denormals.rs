@@ -0,0 +1,37 @@
+// Filters ringing out into silence must not slow down: on x86 the decaying tail would
+// reach denormal numbers, which take many times longer to compute with
+
+use std::time::{Duration, Instant};
+use synthia::audio::integrated_loudness;
+
+const SAMPLE_RATE: u32 = 44100;
+const SECONDS: usize = 20;
+// Generous, so a busy machine doesn't fail the test
+const MAX_SLOWDOWN: u32 = 3;
+
+fn tone(seconds: usize) -> Vec<f32> {
+    (0..seconds * SAMPLE_RATE as usize).map(|t| 0.5 * (t as f32 * 0.05).sin()).collect()
+}
+
+// The fastest of a few runs, to keep scheduling noise out of the comparison
+fn time_loudness(samples: &[f32]) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            integrated_loudness(samples, 1, SAMPLE_RATE);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+#[test]
+fn decaying_tail_is_as_fast_as_signal() {
+    let signal = tone(SECONDS);
+    let mut tail = tone(1);
+    tail.resize(signal.len(), 0.0);
+
+    let signal_time = time_loudness(&signal);
+    let tail_time = time_loudness(&tail);
+    assert!(tail_time < signal_time * MAX_SLOWDOWN, "measuring a decaying tail took {:?}, the same length of signal {:?}", tail_time, signal_time);
+}
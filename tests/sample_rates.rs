@@ -0,0 +1,57 @@
+// Instruments are defined in seconds and Hz, so a note must sound the same whatever
+// the sample rate it is rendered at
+
+use synthia::audio::generate_wave_from_packets;
+use synthia::song::{packets_from_notes, Beats, Instrument, Note};
+
+const RATES: [u32; 4] = [22050, 44100, 48000, 96000];
+const REFERENCE_RATE: u32 = 44100;
+const BPM: f32 = 120.0;
+
+// Duration in seconds, RMS level and zero crossings per second of a render
+struct Features {
+    duration: f32,
+    rms: f32,
+    crossings: f32,
+}
+
+fn render(instrument: Instrument, pitch: u8, sample_rate: u32) -> Vec<f32> {
+    let note = Note { start: Beats::ZERO, duration: Beats::whole(2), pitch, instrument, velocity: 0.5, articulations: Vec::new(), envelope: None, pan: 0.0 };
+    // A silent note at the end, so the song is long enough for the instrument's tail
+    let rest = Note { start: Beats::whole(7), duration: Beats::whole(1), pitch, instrument: Instrument::Sine, velocity: 0.0, articulations: Vec::new(), envelope: None, pan: 0.0 };
+    generate_wave_from_packets(&packets_from_notes(&[note, rest]), BPM, sample_rate).1
+}
+
+fn features(samples: &[f32], sample_rate: u32) -> Features {
+    let duration = samples.len() as f32 / sample_rate as f32;
+    let rms = (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt();
+    let crossings = samples.windows(2).filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0)).count() as f32 / duration;
+    Features { duration, rms, crossings }
+}
+
+fn assert_close(what: &str, instrument: &Instrument, sample_rate: u32, value: f32, reference: f32, tolerance: f32) {
+    assert!(
+        (value - reference).abs() <= tolerance * reference.abs(),
+        "{} of {} at {} Hz is {}, {} at {} Hz",
+        what, instrument, sample_rate, value, reference, REFERENCE_RATE
+    );
+}
+
+#[test]
+fn instruments_sound_the_same_at_every_sample_rate() {
+    let instruments = [Instrument::Sine, Instrument::Square, Instrument::Triangle, Instrument::Saw, Instrument::Piano];
+
+    for instrument in instruments {
+        let reference = features(&render(instrument.clone(), 57, REFERENCE_RATE), REFERENCE_RATE);
+
+        for sample_rate in RATES {
+            let samples = render(instrument.clone(), 57, sample_rate);
+            assert!(samples.iter().all(|sample| sample.is_finite()), "{} renders non-finite samples at {} Hz", instrument, sample_rate);
+
+            let rendered = features(&samples, sample_rate);
+            assert_close("Duration", &instrument, sample_rate, rendered.duration, reference.duration, 0.001);
+            assert_close("RMS", &instrument, sample_rate, rendered.rms, reference.rms, 0.05);
+            assert_close("Zero crossing rate", &instrument, sample_rate, rendered.crossings, reference.crossings, 0.05);
+        }
+    }
+}